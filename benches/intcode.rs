@@ -0,0 +1,82 @@
+// Running times of the interpreter and of compiled programs on the runs of the day2 search and of
+// the day5 diagnostics. Run with `cargo bench`.
+use hohoho::intcode::compiled::Compiled;
+use hohoho::intcode::{parse_program, Machine};
+use std::collections::VecDeque;
+use std::hint::black_box;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// ROUNDS is the number of times each workload runs, the fastest round being reported.
+const ROUNDS: usize = 10;
+
+/// fastest returns the shortest time taken by `workload` over ROUNDS rounds.
+fn fastest(mut workload: impl FnMut()) -> Duration {
+    (0..ROUNDS)
+        .map(|_| {
+            let start = Instant::now();
+            workload();
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+/// day2 runs the gravity assist program with every noun and verb.
+fn day2(new_machine: &dyn Fn() -> Machine) -> i64 {
+    let mut sum = 0;
+    for noun in 0..=99 {
+        for verb in 0..=99 {
+            let mut machine = new_machine();
+            machine.write(1, noun);
+            machine.write(2, verb);
+            machine.run(&mut VecDeque::new(), &mut vec![]).unwrap();
+            sum += machine.read(0) % 2;
+        }
+    }
+    sum
+}
+
+/// day5 runs the diagnostics of the thermal radiator controller 1000 times.
+fn day5(new_machine: &dyn Fn() -> Machine) -> i64 {
+    let mut outputs: Vec<i64> = vec![];
+    for _ in 0..1000 {
+        let mut machine = new_machine();
+        machine
+            .run(&mut VecDeque::from(vec![5]), &mut outputs)
+            .unwrap();
+    }
+    outputs.iter().sum()
+}
+
+fn report(name: &str, interpreted: Duration, compiled: Duration) {
+    println!(
+        "{:<6} interpreted {:>10.3?}  compiled {:>10.3?}  speedup {:.1}x",
+        name,
+        interpreted,
+        compiled,
+        interpreted.as_secs_f64() / compiled.as_secs_f64()
+    );
+}
+
+fn main() {
+    let program: Vec<i64> = parse_program(include_str!("../input/2019/day2.txt")).unwrap();
+    let compiled = Arc::new(Compiled::new(&program));
+    let interpreted = fastest(|| {
+        black_box(day2(&|| Machine::new(&program)));
+    });
+    let fast = fastest(|| {
+        black_box(day2(&|| Machine::from_compiled(Arc::clone(&compiled))));
+    });
+    report("day2", interpreted, fast);
+
+    let program: Vec<i64> = parse_program(include_str!("../input/2019/day5.txt")).unwrap();
+    let compiled = Arc::new(Compiled::new(&program));
+    let interpreted = fastest(|| {
+        black_box(day5(&|| Machine::new(&program)));
+    });
+    let fast = fastest(|| {
+        black_box(day5(&|| Machine::from_compiled(Arc::clone(&compiled))));
+    });
+    report("day5", interpreted, fast);
+}
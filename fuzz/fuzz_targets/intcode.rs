@@ -0,0 +1,7 @@
+// Checks the properties of hohoho::intcode::fuzz on the programs libFuzzer comes up with.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    hohoho::intcode::fuzz::check_bytes(data);
+});
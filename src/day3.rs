@@ -2,8 +2,8 @@
 use aoc_runner_derive::{aoc, aoc_generator};
 use std::num::ParseIntError;
 
-#[derive(Clone, Debug, PartialEq, Eq)]
-enum Direction {
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Direction {
     Up,
     Down,
     Left,
@@ -22,10 +22,30 @@ impl From<char> for Direction {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-struct Position {
-    x: i32,
-    y: i32,
+impl Direction {
+    pub fn turn_left(self) -> Self {
+        match self {
+            Direction::Up => Direction::Left,
+            Direction::Left => Direction::Down,
+            Direction::Down => Direction::Right,
+            Direction::Right => Direction::Up,
+        }
+    }
+
+    pub fn turn_right(self) -> Self {
+        match self {
+            Direction::Up => Direction::Right,
+            Direction::Right => Direction::Down,
+            Direction::Down => Direction::Left,
+            Direction::Left => Direction::Up,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Position {
+    pub x: i32,
+    pub y: i32,
 }
 
 impl Position {
@@ -36,6 +56,28 @@ impl Position {
         let y2 = self.y.wrapping_abs() as u32;
         (x1 + x2) + (y1 + y2)
     }
+
+    /// moved returns the neighbour of the position in `direction`, up being towards +y.
+    pub fn moved(&self, direction: Direction) -> Position {
+        match direction {
+            Direction::Up => Position {
+                x: self.x,
+                y: self.y + 1,
+            },
+            Direction::Down => Position {
+                x: self.x,
+                y: self.y - 1,
+            },
+            Direction::Right => Position {
+                x: self.x + 1,
+                y: self.y,
+            },
+            Direction::Left => Position {
+                x: self.x - 1,
+                y: self.y,
+            },
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -207,6 +249,16 @@ mod tests {
         assert_eq!(anchor.manhattan_distance(test), 6)
     }
 
+    #[test]
+    fn test_turn_and_move() {
+        let direction = Direction::Up.turn_right();
+        assert_eq!(direction, Direction::Right);
+        assert_eq!(direction.turn_left().turn_left(), Direction::Left);
+        let anchor = Position { x: 0, y: 0 };
+        assert_eq!(anchor.moved(direction), Position { x: 1, y: 0 });
+        assert_eq!(anchor.moved(Direction::Down), Position { x: 0, y: -1 });
+    }
+
     #[test]
     fn test_move_from_string() {
         let input = String::from("U78");
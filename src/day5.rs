@@ -1,281 +1,145 @@
 // https://adventofcode.com/2019/day/5
 use aoc_runner_derive::{aoc, aoc_generator};
+use crate::intcode::{self, Input, IntcodeError, Machine, Output};
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
 use std::num::ParseIntError;
-use std::str::FromStr;
-use std::io::{self, BufRead};
 
-const ADD_ARGS_EXPECTED:usize  = 3;
-const MUL_ARGS_EXPECTED:usize  = 3;
-const INS_ARGS_EXPECTED:usize  = 1;
-const OUTPUT_ARGS_EXPECTED: usize = 1;
-const HALT_ARGS_EXPECTED: usize = 0;
-
-const POSITION_MODE: i32 = 0;
-// const INSERT_MODE: i32 = 1;
-
-
-fn add(mut instructions: Vec<i32>, args: Vec<i32>) -> Vec<i32> {
-    assert_eq!(args.len(), 3);
-    let input1 = args[0];
-    let input2 = args[1];
-    let to_store_position = args[2] as usize;
-    instructions[to_store_position] = input1 + input2;
-    instructions
-}
-
-fn mul(mut instructions: Vec<i32>, args: Vec<i32>) -> Vec<i32> {
-    assert_eq!(args.len(), 3);
-    let input1 = args[0];
-    let input2 = args[1];
-    let to_store_position = args[2] as usize;
-    instructions[to_store_position] = input1 * input2;
-    instructions
-}
-
-fn ins(mut  instructions: Vec<i32>, args: Vec<i32>) -> Vec<i32> {
-    assert_eq!(args.len(), 1);
-    let input: i32 = args[0];
-    let mut buffer = String::new();
-    let stdin = io::stdin();
-    stdin.lock().read_line(&mut buffer).expect("Could not read line");
-    instructions[input as usize] = buffer.trim().parse().unwrap();
-    instructions
-}
-
-fn output(instructions: Vec<i32>, args: Vec<i32>) -> Vec<i32> {
-    println!("{}", args[0]);
-    instructions
-}
-
-fn halt(instructions: Vec<i32>, _args: Vec<i32>) -> Vec<i32> {
-    instructions
-}
-
-fn jump_if_true(args: Vec<i32>, position: usize) -> usize {
-    let input1 = args[0];
-    let input2 = args[1];
-    if input1 != 0 {
-       return input2 as usize
-    }
-    position
-}
-
-fn jump_if_false(args: Vec<i32>, position: usize) -> usize {
-    let input1 = args[0];
-    let input2 = args[1];
-    if input1 == 0 {
-        return input2 as usize
-    }
-    position
-}
-
-fn equals(mut instructions: Vec<i32>, args: Vec<i32>) -> Vec<i32> {
-    let input1 = args[0];
-    let input2 = args[1];
-    let input3 = args[2];
-    if input1 == input2 {
-        instructions[input3 as usize] = 1
-    } else {
-        instructions[input3 as usize ] = 0
-    }
-    instructions
-}
-
-fn less_than(mut instructions: Vec<i32>, args: Vec<i32>) -> Vec<i32> {
-    let input1 = args[0];
-    let input2 = args[1];
-    let input3 = args[2];
-    if input1 < input2 {
-        instructions[input3 as usize] = 1
-    } else  {
-        instructions[input3 as usize] = 0
-    }
-    instructions
-
-}
+const AIR_CONDITIONER_UNIT_ID: i64 = 1;
+const THERMAL_RADIATOR_CONTROLLER_ID: i64 = 5;
+/// The diagnostic programs execute a few hundred instructions.
+const STEP_BUDGET: usize = 1_000_000;
 
 #[derive(Debug, PartialEq, Eq)]
-enum Operation {
-    Add,
-    Mul,
-    Ins,
-    Out,
-    JumpIfTrue,
-    JumpIfFalse,
-    LessThan,
-    Equals,
-    Halt,
-}
-
-impl From<i32> for Operation {
-    fn from(input: i32) -> Self {
-        match input {
-            1 => Operation::Add,
-            2 => Operation::Mul,
-            3 => Operation::Ins,
-            4 => Operation::Out,
-            5 => Operation::JumpIfTrue,
-            6 => Operation::JumpIfFalse,
-            7 => Operation::LessThan,
-            8 => Operation::Equals,
-            99 => Operation::Halt,
-            _ => panic!("Operation not known!"),
-        }
-    }
+pub enum DiagnosticError {
+    /// A test before the diagnostic code did not output 0.
+    FailedCheck { check: usize, output: i64 },
+    NoDiagnosticCode,
+    Intcode(IntcodeError),
 }
 
-impl Operation {
-    fn get_args_expected(&self) -> usize {
-        match self {
-            Operation::Add => ADD_ARGS_EXPECTED,
-            Operation::Mul => MUL_ARGS_EXPECTED,
-            Operation::Ins => INS_ARGS_EXPECTED,
-            Operation::Out => OUTPUT_ARGS_EXPECTED,
-            Operation::Halt => HALT_ARGS_EXPECTED,
-            Operation::JumpIfTrue => 2,
-            Operation::JumpIfFalse => 2,
-            Operation::LessThan => 3,
-            Operation::Equals => 3
-        }
-    }
-
-    fn apply(&self, inputs: Vec<i32>, instructions: Vec<i32>) -> Vec<i32> {
-        match self {
-            Operation::Add => add(inputs, instructions),
-            Operation::Mul => mul(inputs, instructions),
-            Operation::Ins => ins(inputs, instructions),
-            Operation::Out => output(inputs, instructions),
-            Operation::Halt => halt(inputs, instructions),
-            Operation::LessThan => less_than(inputs, instructions),
-            Operation::Equals => equals(inputs, instructions),
-            _ => panic!("Can't apply for jump commands"),
-        }
-    }
-
-    fn jump(&self, instructions: Vec<i32>, position: usize) -> usize {
+impl fmt::Display for DiagnosticError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Operation::JumpIfFalse => jump_if_false(instructions, position),
-            Operation::JumpIfTrue => jump_if_true(instructions, position),
-            _ => panic!("Can't jump for operation {:?}", self)
-        }
-    }
-
-
-    fn is_write_parameter(&self, parameter_position: usize) -> bool {
-        match (self, parameter_position) {
-            (Operation::Add, 2) => true,
-            (Operation::Mul, 2) => true,
-            (Operation::Ins, 0) => true,
-            (Operation::LessThan, 2) => true,
-            (Operation::Equals, 2) => true,
-            _ => false,
+            DiagnosticError::FailedCheck { check, output } => {
+                write!(f, "diagnostic check #{} failed with output {}", check, output)
+            }
+            DiagnosticError::NoDiagnosticCode => write!(f, "the program did not output any diagnostic code"),
+            DiagnosticError::Intcode(error) => write!(f, "the diagnostic program crashed: {}", error),
         }
     }
 }
 
-fn read_arguments(inputs: &Vec<i32>, progress: usize) -> (Operation, Vec<i32>, usize) {
-    // hole contains opcode + instruction mode
-    let hole: i32 = inputs[progress];
-    let opcode: i32 = hole % 100;
-    let mut instruction_modes: i32 = hole / 100;
-    let operation: Operation = Operation::from(opcode);
-    let args_expected: usize = operation.get_args_expected();
-    let mut instructions_to_process = args_expected;
-    let mut res: Vec<i32> = vec![];
-    while instructions_to_process != 0 {
-        let mode = instruction_modes % 10;
-        let input: i32 = inputs[progress + 1 + args_expected - instructions_to_process];
+impl Error for DiagnosticError {}
 
-        if !operation.is_write_parameter(args_expected - instructions_to_process) && mode == POSITION_MODE {
-            res.push(inputs[input as usize]);
-        } else {
-            res.push(input);
-        }
-        instructions_to_process -= 1;
-        instruction_modes = instruction_modes / 10;
+impl From<IntcodeError> for DiagnosticError {
+    fn from(error: IntcodeError) -> Self {
+        DiagnosticError::Intcode(error)
     }
-    (operation, res, progress + args_expected + 1)
 }
 
 #[aoc_generator(day5)]
-fn parse_input(input: &str) -> Result<Vec<i32>, ParseIntError> {
-    input.split(",").map(|i| i32::from_str(i)).collect()
+fn parse_input(input: &str) -> Result<Vec<i64>, ParseIntError> {
+    intcode::parse_program(input)
 }
 
-fn intcode_program(mut input: Vec<i32>) -> Vec<i32> {
-    //input[1] = noun;
-    //input[2] = verb;
-    let mut line_start_index: usize = 0;
-    while line_start_index < input.len() {
-        let (operation, instruction_parameters, new_index) = read_arguments(&input, line_start_index);
-        line_start_index = new_index;
+fn intcode_program(input: Vec<i64>, ins: &mut dyn Input, out: &mut dyn Output) -> Result<Vec<i64>, IntcodeError> {
+    let mut machine = Machine::new(&input);
+    machine.limit_steps(STEP_BUDGET);
+    machine.detect_loops();
+    machine.run(ins, out)?;
+    Ok(machine.into_memory())
+}
 
-        match operation {
-            Operation::Halt => {
-                return input;
-            },
-            Operation::JumpIfFalse | Operation::JumpIfTrue => {
-                line_start_index = operation.jump(instruction_parameters, line_start_index);
-            }
-            _ => {
-                input = operation.apply(input, instruction_parameters);
-            }
-        }
+/// diagnostic_code runs the diagnostic program for the system `system_id` and returns the
+/// diagnostic code, making sure every test output before it is 0.
+fn diagnostic_code(input: &[i64], system_id: i64) -> Result<i64, DiagnosticError> {
+    let mut outputs: Vec<i64> = vec![];
+    intcode_program(Vec::from(input), &mut VecDeque::from(vec![system_id]), &mut outputs)?;
+    let (code, checks) = outputs.split_last().ok_or(DiagnosticError::NoDiagnosticCode)?;
+    if let Some((check, &output)) = checks.iter().enumerate().find(|(_, &output)| output != 0) {
+        return Err(DiagnosticError::FailedCheck { check, output });
     }
-    return input;
+    Ok(*code)
 }
 
 #[aoc(day5, part1)]
-pub fn part1(input: &[i32]) -> String {
-    let mut result: Vec<i32> = Vec::from(input);
-    result = intcode_program(result);
-    return format!("{:?}", result);
+pub fn part1(input: &[i64]) -> Result<i64, DiagnosticError> {
+    diagnostic_code(input, AIR_CONDITIONER_UNIT_ID)
 }
 
 #[aoc(day5, part2)]
-pub fn part2(input: &[i32]) -> String {
-    let mut result: Vec<i32> = Vec::from(input);
-    result = intcode_program(result);
-    return format!("{:?}", result);
+pub fn part2(input: &[i64]) -> Result<i64, DiagnosticError> {
+    diagnostic_code(input, THERMAL_RADIATOR_CONTROLLER_ID)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn run(program: Vec<i64>, inputs: Vec<i64>) -> Vec<i64> {
+        let mut outputs = vec![];
+        intcode_program(program, &mut VecDeque::from(inputs), &mut outputs).unwrap();
+        outputs
+    }
+
     #[test]
-    fn test_add_operation() {
-        let op = Operation::Add;
-        assert_eq!(op.apply(vec![1,1,1], vec![4, 1, 2]), vec![1, 1, 5]);
+    fn test_parse_input() {
+        assert_eq!(parse_input("1002,4,3,4,33"), Ok(vec![1002, 4, 3, 4, 33]));
     }
 
     #[test]
-    fn test_mul_operation() {
-        let op = Operation::Mul;
-        assert_eq!(op.apply(vec![1,1,1], vec![4, 2, 0]), vec![8, 1, 1]);
+    fn test_intcode_program() {
+        let (mut ins, mut out) = (VecDeque::new(), vec![]);
+        assert_eq!(intcode_program(vec![1,0,0,0,99], &mut ins, &mut out), Ok(vec![2, 0, 0, 0, 99]));
+        assert_eq!(intcode_program(vec![2,3,0,3,99 ], &mut ins, &mut out), Ok(vec![2, 3, 0, 6, 99]));
+        assert_eq!(intcode_program(vec![2,4,4,5,99,0 ], &mut ins, &mut out), Ok(vec![2, 4, 4, 5, 99, 9801]));
+        assert_eq!(intcode_program(vec![1,1,1,4,99,5,6,0,99 ], &mut ins, &mut out), Ok(vec![30, 1, 1, 4, 2, 5, 6, 0, 99]));
     }
 
     #[test]
-    fn test_read_arguments() {
-        let (operation, instruction_args, index) = read_arguments(&vec![1002, 1, 2, 3], 0);
-        assert_eq!(instruction_args, vec![1, 2, 3]);
-        assert_eq!(operation, Operation::Mul);
-        assert_eq!(index, 4);
+    fn test_infinite_loop() {
+        let (mut ins, mut out) = (VecDeque::new(), vec![]);
+        // JT #1, #3 then JT #1, #0
+        assert_eq!(
+            intcode_program(vec![1105, 1, 3, 1105, 1, 0], &mut ins, &mut out),
+            Err(IntcodeError::InfiniteLoop { address: 3, addresses: 0..6, period: 2 })
+        );
     }
 
     #[test]
-    fn test_read_arguments_with_one_opcode() {
-        let (operation, instruction_args, index) = read_arguments(&vec![1, 2, 0, 2], 0);
-        assert_eq!(instruction_args, vec![0, 1, 2]);
-        assert_eq!(operation, Operation::Add);
-        assert_eq!(index, 4);
+    fn test_larger_example() {
+        let program = vec![
+            3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,
+            1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,
+            999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99,
+        ];
+        assert_eq!(run(program.clone(), vec![7]), vec![999]);
+        assert_eq!(run(program.clone(), vec![8]), vec![1000]);
+        assert_eq!(run(program, vec![9]), vec![1001]);
     }
 
     #[test]
-    fn test_intcode_program() {
-        assert_eq!(intcode_program(vec![1,0,0,0,99]), vec![2, 0, 0, 0, 99]);
-        assert_eq!(intcode_program(vec![2,3,0,3,99 ]), vec![2, 3, 0, 6, 99]);
-        assert_eq!(intcode_program(vec![2,4,4,5,99,0 ]), vec![2, 4, 4, 5, 99, 9801]);
-        assert_eq!(intcode_program(vec![1,1,1,4,99,5,6,0,99 ]), vec![30, 1, 1, 4, 2, 5, 6, 0, 99]);
+    fn test_diagnostic_code() {
+        // outputs 0, then the input, then 7
+        let program = vec![104, 0, 3, 11, 4, 11, 104, 7, 99, 0, 0, 0];
+        assert_eq!(diagnostic_code(&program, 0), Ok(7));
+        assert_eq!(
+            diagnostic_code(&program, 3),
+            Err(DiagnosticError::FailedCheck { check: 1, output: 3 })
+        );
+        assert_eq!(diagnostic_code(&[99], 1), Err(DiagnosticError::NoDiagnosticCode));
+        assert_eq!(
+            diagnostic_code(&[3, -1, 99], 1),
+            Err(DiagnosticError::Intcode(IntcodeError::NegativeAddress { address: 0, target: -1 }))
+        );
+    }
+
+    #[test]
+    fn test_part1_and_part2() {
+        let program = parse_input(include_str!("../input/2019/day5.txt")).unwrap();
+        assert_eq!(part1(&program), Ok(6745903));
+        assert_eq!(part2(&program), Ok(9168267));
     }
 }
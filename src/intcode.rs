@@ -0,0 +1,1222 @@
+// Intcode computer shared by https://adventofcode.com/2019/day/2 and https://adventofcode.com/2019/day/5
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::io::{self, BufRead};
+use std::mem;
+use std::str::FromStr;
+use std::sync::Arc;
+
+pub mod amplifier;
+pub mod analysis;
+pub mod ascii;
+pub mod assembler;
+pub mod compiled;
+pub mod conformance;
+pub mod debugger;
+pub mod differential;
+pub mod disassembler;
+mod error;
+#[cfg(any(test, feature = "fuzzing"))]
+pub mod fuzz;
+mod loops;
+mod memory;
+pub mod modification;
+pub mod network;
+pub mod robot;
+pub mod search;
+pub mod snapshot;
+pub mod symbolic;
+pub mod trace;
+mod word;
+
+use compiled::{CodeCache, Compiled};
+pub use error::IntcodeError;
+use loops::{LoopDetector, Reads};
+pub use memory::Memory;
+use modification::{CodeWrite, CodeWriteDetector};
+use snapshot::Snapshot;
+use trace::{Operand, Trace, TraceEntry};
+pub use word::Word;
+
+const ADD_ARGS_EXPECTED: usize = 3;
+const MUL_ARGS_EXPECTED: usize = 3;
+const INS_ARGS_EXPECTED: usize = 1;
+const OUTPUT_ARGS_EXPECTED: usize = 1;
+const JUMP_ARGS_EXPECTED: usize = 2;
+const COMPARISON_ARGS_EXPECTED: usize = 3;
+const ADJUST_RELATIVE_BASE_ARGS_EXPECTED: usize = 1;
+const HALT_ARGS_EXPECTED: usize = 0;
+
+const POSITION_MODE: i64 = 0;
+const IMMEDIATE_MODE: i64 = 1;
+const RELATIVE_MODE: i64 = 2;
+
+const OPERATIONS: [Operation; 10] = [
+    Operation::Add,
+    Operation::Mul,
+    Operation::Ins,
+    Operation::Out,
+    Operation::JumpIfTrue,
+    Operation::JumpIfFalse,
+    Operation::LessThan,
+    Operation::Equals,
+    Operation::AdjustRelativeBase,
+    Operation::Halt,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Operation {
+    Add,
+    Mul,
+    Ins,
+    Out,
+    JumpIfTrue,
+    JumpIfFalse,
+    LessThan,
+    Equals,
+    AdjustRelativeBase,
+    Halt,
+}
+
+impl Operation {
+    /// from_opcode returns the operation of an opcode, without its parameter modes.
+    pub fn from_opcode(opcode: i64) -> Option<Self> {
+        OPERATIONS
+            .iter()
+            .find(|operation| operation.opcode() == opcode)
+            .copied()
+    }
+
+    pub fn opcode(&self) -> i64 {
+        match self {
+            Operation::Add => 1,
+            Operation::Mul => 2,
+            Operation::Ins => 3,
+            Operation::Out => 4,
+            Operation::JumpIfTrue => 5,
+            Operation::JumpIfFalse => 6,
+            Operation::LessThan => 7,
+            Operation::Equals => 8,
+            Operation::AdjustRelativeBase => 9,
+            Operation::Halt => 99,
+        }
+    }
+
+    /// from_mnemonic is the inverse of mnemonic, ignoring case.
+    pub fn from_mnemonic(mnemonic: &str) -> Option<Self> {
+        OPERATIONS
+            .iter()
+            .find(|operation| operation.mnemonic().eq_ignore_ascii_case(mnemonic))
+            .copied()
+    }
+
+    /// mnemonic returns the name of the operation in assembly listings.
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Operation::Add => "ADD",
+            Operation::Mul => "MUL",
+            Operation::Ins => "IN",
+            Operation::Out => "OUT",
+            Operation::JumpIfTrue => "JT",
+            Operation::JumpIfFalse => "JF",
+            Operation::LessThan => "LT",
+            Operation::Equals => "EQ",
+            Operation::AdjustRelativeBase => "ARB",
+            Operation::Halt => "HLT",
+        }
+    }
+
+    pub fn get_args_expected(&self) -> usize {
+        match self {
+            Operation::Add => ADD_ARGS_EXPECTED,
+            Operation::Mul => MUL_ARGS_EXPECTED,
+            Operation::Ins => INS_ARGS_EXPECTED,
+            Operation::Out => OUTPUT_ARGS_EXPECTED,
+            Operation::JumpIfTrue | Operation::JumpIfFalse => JUMP_ARGS_EXPECTED,
+            Operation::LessThan | Operation::Equals => COMPARISON_ARGS_EXPECTED,
+            Operation::AdjustRelativeBase => ADJUST_RELATIVE_BASE_ARGS_EXPECTED,
+            Operation::Halt => HALT_ARGS_EXPECTED,
+        }
+    }
+
+    /// is_write_parameter tells if the parameter at `parameter_position` is an address the
+    /// operation writes to.
+    pub fn is_write_parameter(&self, parameter_position: usize) -> bool {
+        matches!(
+            (self, parameter_position),
+            (Operation::Add, 2)
+                | (Operation::Mul, 2)
+                | (Operation::Ins, 0)
+                | (Operation::LessThan, 2)
+                | (Operation::Equals, 2)
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Position,
+    Immediate,
+    Relative,
+}
+
+impl Mode {
+    pub fn digit(&self) -> i64 {
+        match self {
+            Mode::Position => POSITION_MODE,
+            Mode::Immediate => IMMEDIATE_MODE,
+            Mode::Relative => RELATIVE_MODE,
+        }
+    }
+
+    pub fn from_digit(digit: i64) -> Option<Self> {
+        match digit {
+            POSITION_MODE => Some(Mode::Position),
+            IMMEDIATE_MODE => Some(Mode::Immediate),
+            RELATIVE_MODE => Some(Mode::Relative),
+            _ => None,
+        }
+    }
+}
+
+/// Input is the source the machine reads from on `Ins` operations.
+pub trait Input<W = i64> {
+    /// read returns the next value, or None if there is nothing left to read.
+    fn read(&mut self) -> Option<W>;
+}
+
+/// Output is the sink the machine writes to on `Out` operations.
+pub trait Output<W = i64> {
+    fn write(&mut self, value: W);
+}
+
+impl<W> Input<W> for VecDeque<W> {
+    fn read(&mut self) -> Option<W> {
+        self.pop_front()
+    }
+}
+
+/// NonBlockingInput is an input queue which reads a default value instead of blocking when it is
+/// empty, like the network interfaces which read -1 when no packet is waiting.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NonBlockingInput<W = i64> {
+    queue: VecDeque<W>,
+    empty: W,
+    empty_reads: usize,
+}
+
+impl<W: Clone> NonBlockingInput<W> {
+    pub fn new(empty: W) -> Self {
+        NonBlockingInput {
+            queue: VecDeque::new(),
+            empty,
+            empty_reads: 0,
+        }
+    }
+
+    pub fn push(&mut self, value: W) {
+        self.queue.push_back(value);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// empty_reads returns how many times the default value was read.
+    pub fn empty_reads(&self) -> usize {
+        self.empty_reads
+    }
+}
+
+impl<W: Clone> Input<W> for NonBlockingInput<W> {
+    fn read(&mut self) -> Option<W> {
+        match self.queue.pop_front() {
+            Some(value) => Some(value),
+            None => {
+                self.empty_reads += 1;
+                Some(self.empty.clone())
+            }
+        }
+    }
+}
+
+impl<W: FromStr> Input<W> for io::Stdin {
+    fn read(&mut self) -> Option<W> {
+        let mut buffer = String::new();
+        self.lock().read_line(&mut buffer).ok()?;
+        buffer.trim().parse().ok()
+    }
+}
+
+impl<W> Output<W> for Vec<W> {
+    fn write(&mut self, value: W) {
+        self.push(value);
+    }
+}
+
+impl<W> Output<W> for VecDeque<W> {
+    fn write(&mut self, value: W) {
+        self.push_back(value);
+    }
+}
+
+impl<W: Display> Output<W> for io::Stdout {
+    fn write(&mut self, value: W) {
+        println!("{}", value);
+    }
+}
+
+/// Parameter is a raw parameter of an instruction, as written in memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Parameter<W = i64> {
+    pub mode: Mode,
+    pub value: W,
+}
+
+/// Instruction is an operation decoded from memory, with its parameters not resolved yet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction<W = i64> {
+    pub operation: Operation,
+    pub parameters: Vec<Parameter<W>>,
+}
+
+impl<W: Word> Instruction<W> {
+    /// decode reads the instruction starting at `address`.
+    pub fn decode(memory: &Memory<W>, address: usize) -> Result<Instruction<W>, IntcodeError<W>> {
+        // hole contains opcode + instruction mode
+        let word = memory.read(address);
+        let unknown_opcode = || IntcodeError::UnknownOpcode {
+            address,
+            opcode: word.clone(),
+        };
+        let hole: i64 = word.to_i64().ok_or_else(unknown_opcode)?;
+        let operation = Operation::from_opcode(hole % 100).ok_or_else(unknown_opcode)?;
+        let mut instruction_modes: i64 = hole / 100;
+        let mut parameters: Vec<Parameter<W>> = vec![];
+        for i in 0..operation.get_args_expected() {
+            let digit = instruction_modes % 10;
+            let mode = Mode::from_digit(digit).ok_or(IntcodeError::InvalidParameterMode {
+                address,
+                parameter: i,
+                mode: digit,
+            })?;
+            parameters.push(Parameter {
+                mode,
+                value: memory.read(address + 1 + i),
+            });
+            instruction_modes /= 10;
+        }
+        Ok(Instruction {
+            operation,
+            parameters,
+        })
+    }
+
+    /// size returns the number of memory cells taken by the instruction.
+    pub fn size(&self) -> usize {
+        self.parameters.len() + 1
+    }
+}
+
+/// Event is what interrupted a machine driven by Machine::run_until_event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event<W = i64> {
+    /// The machine is waiting on an `Ins` instruction, see Machine::push_input.
+    NeedsInput,
+    Output(W),
+    Halted,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Machine<W = i64> {
+    memory: Memory<W>,
+    ip: usize,
+    relative_base: i64,
+    halted: bool,
+    inputs: VecDeque<W>,
+    trace: Option<Trace<W>>,
+    code_writes: Option<CodeWriteDetector>,
+    /// The number of instructions executed, and how many may be.
+    steps: usize,
+    step_budget: Option<usize>,
+    code: Option<CodeCache<W>>,
+    loops: Option<LoopDetector<W>>,
+}
+
+impl<W: Word> Machine<W> {
+    pub fn new(program: &[W]) -> Self {
+        Machine::with_memory(Memory::from(program))
+    }
+
+    fn with_memory(memory: Memory<W>) -> Self {
+        Machine {
+            memory,
+            ip: 0,
+            relative_base: 0,
+            halted: false,
+            inputs: VecDeque::new(),
+            trace: None,
+            code_writes: None,
+            steps: 0,
+            step_budget: None,
+            code: None,
+            loops: None,
+        }
+    }
+
+    /// from_compiled creates a machine running a compiled program, decoding only the instructions
+    /// the program modified. The machine shares the memory of the program until it writes to it.
+    pub fn from_compiled(compiled: Arc<Compiled<W>>) -> Self {
+        let mut machine = Machine::with_memory(compiled.memory().clone());
+        machine.code = Some(CodeCache::new(compiled));
+        machine
+    }
+
+    /// snapshot returns the state of the machine, to resume it with from_snapshot. Tracing, the
+    /// compiled program, the step count and budget and loop detection are not part of it.
+    pub fn snapshot(&self) -> Snapshot<W> {
+        Snapshot {
+            memory: self.memory.clone(),
+            ip: self.ip,
+            relative_base: self.relative_base,
+            halted: self.halted,
+            inputs: self.inputs.clone(),
+        }
+    }
+
+    pub fn from_snapshot(snapshot: Snapshot<W>) -> Self {
+        Machine {
+            ip: snapshot.ip,
+            relative_base: snapshot.relative_base,
+            halted: snapshot.halted,
+            inputs: snapshot.inputs,
+            ..Machine::with_memory(snapshot.memory)
+        }
+    }
+
+    pub fn memory(&self) -> &Memory<W> {
+        &self.memory
+    }
+
+    /// into_memory returns the dense part of the memory, see Memory::into_vec.
+    pub fn into_memory(self) -> Vec<W> {
+        self.memory.into_vec()
+    }
+
+    pub fn ip(&self) -> usize {
+        self.ip
+    }
+
+    pub fn relative_base(&self) -> i64 {
+        self.relative_base
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    pub fn read(&self, address: usize) -> W {
+        self.memory.read(address)
+    }
+
+    pub fn write(&mut self, address: usize, value: W) {
+        if let Some(code) = &mut self.code {
+            code.invalidate(address);
+        }
+        self.memory.write(address, value);
+    }
+
+    /// enable_trace makes the machine record every instruction it executes from now on.
+    pub fn enable_trace(&mut self) {
+        self.trace.get_or_insert_with(Trace::default);
+    }
+
+    pub fn trace(&self) -> Option<&Trace<W>> {
+        self.trace.as_ref()
+    }
+
+    /// take_trace returns the trace recorded so far and disables tracing.
+    pub fn take_trace(&mut self) -> Option<Trace<W>> {
+        self.trace.take()
+    }
+
+    /// detect_code_writes makes the machine report the writes into cells executed as code, before
+    /// or after the write, from now on.
+    pub fn detect_code_writes(&mut self) {
+        self.code_writes
+            .get_or_insert_with(CodeWriteDetector::default);
+    }
+
+    pub fn code_writes(&self) -> Option<&[CodeWrite]> {
+        self.code_writes
+            .as_ref()
+            .map(|detector| detector.code_writes())
+    }
+
+    /// limit_steps makes the machine fail with StepBudgetExceeded instead of executing more than
+    /// `budget` instructions in total.
+    pub fn limit_steps(&mut self, budget: usize) {
+        self.step_budget = Some(budget);
+    }
+
+    /// steps returns the number of instructions executed so far.
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    /// detect_loops makes the machine fail with InfiniteLoop when it comes back to a state it was
+    /// in, without reading any input in between, from now on. It costs a comparison of the memory
+    /// at every step until the loop is found.
+    pub fn detect_loops(&mut self) {
+        self.loops.get_or_insert_with(LoopDetector::default);
+    }
+
+    /// address_of returns the memory address the parameter `index` of `instruction` points to.
+    fn address_of(
+        &self,
+        instruction: &Instruction<W>,
+        index: usize,
+    ) -> Result<usize, IntcodeError<W>> {
+        let parameter = &instruction.parameters[index];
+        let target = match parameter.mode {
+            Mode::Position => self.offset(&parameter.value)?,
+            Mode::Relative => self
+                .relative_base
+                .checked_add(self.offset(&parameter.value)?)
+                .ok_or(IntcodeError::Overflow { address: self.ip })?,
+            Mode::Immediate => {
+                return Err(IntcodeError::WriteInImmediateMode {
+                    address: self.ip,
+                    parameter: index,
+                })
+            }
+        };
+        self.to_address(target)
+    }
+
+    /// value_of returns the value the parameter `index` of `instruction` stands for.
+    fn value_of(&self, instruction: &Instruction<W>, index: usize) -> Result<W, IntcodeError<W>> {
+        match instruction.parameters[index].mode {
+            Mode::Immediate => Ok(instruction.parameters[index].value.clone()),
+            _ => Ok(self.read(self.address_of(instruction, index)?)),
+        }
+    }
+
+    /// offset converts a word used as an address or an offset to the relative base.
+    fn offset(&self, value: &W) -> Result<i64, IntcodeError<W>> {
+        value
+            .to_i64()
+            .ok_or_else(|| IntcodeError::AddressOutOfRange {
+                address: self.ip,
+                target: value.clone(),
+            })
+    }
+
+    fn to_address(&self, target: i64) -> Result<usize, IntcodeError<W>> {
+        if target < 0 {
+            return Err(IntcodeError::NegativeAddress {
+                address: self.ip,
+                target,
+            });
+        }
+        Ok(target as usize)
+    }
+
+    /// operands resolves the parameters of `instruction` in order, stopping at the first one which
+    /// cannot be resolved.
+    fn operands(&self, instruction: &Instruction<W>) -> Vec<Operand<W>> {
+        let mut operands: Vec<Operand<W>> = vec![];
+        for index in 0..instruction.parameters.len() {
+            let operand = if instruction.operation.is_write_parameter(index) {
+                self.address_of(instruction, index).map(Operand::Address)
+            } else {
+                self.value_of(instruction, index).map(Operand::Value)
+            };
+            match operand {
+                Ok(operand) => operands.push(operand),
+                Err(_) => break,
+            }
+        }
+        operands
+    }
+
+    /// step executes the instruction under the instruction pointer. On error the machine is left
+    /// untouched, with the instruction pointer still on the failing instruction, except for
+    /// InfiniteLoop which is found after executing the instruction closing the loop.
+    pub fn step(
+        &mut self,
+        input: &mut dyn Input<W>,
+        output: &mut dyn Output<W>,
+    ) -> Result<(), IntcodeError<W>> {
+        self.check_budget()?;
+        let address = self.ip;
+        if self.loops.is_none() {
+            self.record(input, output)?;
+            self.steps += 1;
+            return Ok(());
+        }
+        let mut input = Reads { input, read: false };
+        self.record(&mut input, output)?;
+        self.steps += 1;
+        let found = match &mut self.loops {
+            Some(detector) => detector.observe(
+                address,
+                input.read,
+                self.ip,
+                self.relative_base,
+                &self.memory,
+            ),
+            None => None,
+        };
+        match found {
+            Some(found) => {
+                let (low, high) = found.starts.into_inner();
+                let end = Instruction::decode(&self.memory, high)
+                    .map(|instruction| high + instruction.size())
+                    .unwrap_or(high + 1);
+                Err(IntcodeError::InfiniteLoop {
+                    address,
+                    addresses: low..end,
+                    period: found.period,
+                })
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn check_budget(&self) -> Result<(), IntcodeError<W>> {
+        match self.step_budget {
+            Some(budget) if self.steps >= budget => Err(IntcodeError::StepBudgetExceeded {
+                address: self.ip,
+                budget,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// record executes the instruction under the instruction pointer, recording it in the trace
+    /// and the code write detector if enabled.
+    fn record(
+        &mut self,
+        input: &mut dyn Input<W>,
+        output: &mut dyn Output<W>,
+    ) -> Result<(), IntcodeError<W>> {
+        if self.trace.is_none() && self.code_writes.is_none() {
+            return self.execute(input, output);
+        }
+        let address = self.ip;
+        let instruction = Instruction::decode(&self.memory, address)?;
+        let operands = self.operands(&instruction);
+        self.execute(input, output)?;
+        let writes: Vec<(usize, W)> = operands
+            .iter()
+            .filter_map(|operand| match operand {
+                Operand::Address(target) => Some((*target, self.read(*target))),
+                Operand::Value(_) => None,
+            })
+            .collect();
+        if let Some(detector) = &mut self.code_writes {
+            let targets: Vec<usize> = writes.iter().map(|(target, _)| *target).collect();
+            detector.record(address, instruction.size(), &targets);
+        }
+        if let Some(trace) = &mut self.trace {
+            trace.record(TraceEntry {
+                address,
+                instruction,
+                operands,
+                writes,
+            });
+        }
+        Ok(())
+    }
+
+    fn execute(
+        &mut self,
+        input: &mut dyn Input<W>,
+        output: &mut dyn Output<W>,
+    ) -> Result<(), IntcodeError<W>> {
+        let mut code = match self.code.take() {
+            Some(code) => code,
+            None => {
+                let instruction = Instruction::decode(&self.memory, self.ip)?;
+                return self.apply(&instruction, input, output).map(|_| ());
+            }
+        };
+        let result = self.execute_compiled(&mut code, input, output);
+        self.code = Some(code);
+        result
+    }
+
+    /// execute_compiled executes the instruction at the instruction pointer from `code`, the
+    /// cache taken out of the machine while the instruction borrowed from it runs.
+    fn execute_compiled(
+        &mut self,
+        code: &mut CodeCache<W>,
+        input: &mut dyn Input<W>,
+        output: &mut dyn Output<W>,
+    ) -> Result<(), IntcodeError<W>> {
+        let written = match code.fetch(self.ip) {
+            Some(op) => self.run_op(op, input, output)?,
+            None => {
+                let instruction = Instruction::decode(&self.memory, self.ip)?;
+                self.apply(&instruction, input, output)?
+            }
+        };
+        if let Some(target) = written {
+            code.invalidate(target);
+        }
+        Ok(())
+    }
+
+    /// apply executes `instruction`, the one at the instruction pointer, and returns the address
+    /// it wrote to.
+    fn apply(
+        &mut self,
+        instruction: &Instruction<W>,
+        input: &mut dyn Input<W>,
+        output: &mut dyn Output<W>,
+    ) -> Result<Option<usize>, IntcodeError<W>> {
+        let address = self.ip;
+        let mut write: Option<(usize, W)> = None;
+        let mut next = address + instruction.size();
+
+        match instruction.operation {
+            Operation::Add => {
+                let value = self
+                    .value_of(instruction, 0)?
+                    .checked_add(&self.value_of(instruction, 1)?)
+                    .ok_or(IntcodeError::Overflow { address })?;
+                let target = self.address_of(instruction, 2)?;
+                write = Some((target, value));
+            }
+            Operation::Mul => {
+                let value = self
+                    .value_of(instruction, 0)?
+                    .checked_mul(&self.value_of(instruction, 1)?)
+                    .ok_or(IntcodeError::Overflow { address })?;
+                let target = self.address_of(instruction, 2)?;
+                write = Some((target, value));
+            }
+            Operation::Ins => {
+                let target = self.address_of(instruction, 0)?;
+                let value = input.read().ok_or(IntcodeError::MissingInput { address })?;
+                write = Some((target, value));
+            }
+            Operation::Out => {
+                output.write(self.value_of(instruction, 0)?);
+            }
+            Operation::JumpIfTrue => {
+                if !self.value_of(instruction, 0)?.is_zero() {
+                    next = self.to_address(self.offset(&self.value_of(instruction, 1)?)?)?;
+                }
+            }
+            Operation::JumpIfFalse => {
+                if self.value_of(instruction, 0)?.is_zero() {
+                    next = self.to_address(self.offset(&self.value_of(instruction, 1)?)?)?;
+                }
+            }
+            Operation::LessThan => {
+                let value =
+                    W::from_bool(self.value_of(instruction, 0)? < self.value_of(instruction, 1)?);
+                let target = self.address_of(instruction, 2)?;
+                write = Some((target, value));
+            }
+            Operation::Equals => {
+                let value =
+                    W::from_bool(self.value_of(instruction, 0)? == self.value_of(instruction, 1)?);
+                let target = self.address_of(instruction, 2)?;
+                write = Some((target, value));
+            }
+            Operation::AdjustRelativeBase => {
+                let offset = self.offset(&self.value_of(instruction, 0)?)?;
+                self.relative_base = self
+                    .relative_base
+                    .checked_add(offset)
+                    .ok_or(IntcodeError::Overflow { address })?;
+            }
+            Operation::Halt => {
+                self.halted = true;
+            }
+        }
+        let written = write.map(|(target, value)| {
+            self.memory.write(target, value);
+            target
+        });
+        self.ip = next;
+        Ok(written)
+    }
+
+    /// run executes the program until it halts or the instruction pointer leaves the memory.
+    pub fn run(
+        &mut self,
+        input: &mut dyn Input<W>,
+        output: &mut dyn Output<W>,
+    ) -> Result<(), IntcodeError<W>> {
+        // Without tracing, the compiled program is taken out of the machine once for the whole run
+        // instead of at every step.
+        if self.trace.is_none() && self.code_writes.is_none() && self.loops.is_none() {
+            if let Some(mut code) = self.code.take() {
+                let mut result = Ok(());
+                while result.is_ok() && !self.halted && self.ip < self.memory.len() {
+                    result = self
+                        .check_budget()
+                        .and_then(|_| self.execute_compiled(&mut code, input, output));
+                    if result.is_ok() {
+                        self.steps += 1;
+                    }
+                }
+                self.code = Some(code);
+                return result;
+            }
+        }
+        while !self.halted && self.ip < self.memory.len() {
+            self.step(input, output)?;
+        }
+        Ok(())
+    }
+
+    /// push_input queues a value for the next `Ins` instructions executed by run_until_event.
+    pub fn push_input(&mut self, value: W) {
+        self.inputs.push_back(value);
+    }
+
+    /// run_until_event executes the program until it outputs a value, halts or needs an input
+    /// which has not been pushed yet. The machine can be resumed by calling it again.
+    pub fn run_until_event(&mut self) -> Result<Event<W>, IntcodeError<W>> {
+        let mut inputs = mem::take(&mut self.inputs);
+        let event = self.next_event(&mut inputs);
+        self.inputs = inputs;
+        event
+    }
+
+    fn next_event(&mut self, inputs: &mut VecDeque<W>) -> Result<Event<W>, IntcodeError<W>> {
+        let mut output: Vec<W> = Vec::with_capacity(1);
+        while !self.halted && self.ip < self.memory.len() {
+            match self.step(inputs, &mut output) {
+                Err(IntcodeError::MissingInput { .. }) => return Ok(Event::NeedsInput),
+                Err(error) => return Err(error),
+                Ok(()) => (),
+            }
+            if let Some(value) = output.pop() {
+                return Ok(Event::Output(value));
+            }
+        }
+        Ok(Event::Halted)
+    }
+}
+
+pub fn parse_program<W: Word>(input: &str) -> Result<Vec<W>, W::Err> {
+    input.trim().split(',').map(W::from_str).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(program: Vec<i64>) -> Vec<i64> {
+        let mut machine = Machine::new(&program);
+        machine.run(&mut VecDeque::new(), &mut vec![]).unwrap();
+        machine.into_memory()
+    }
+
+    fn outputs(program: Vec<i64>, inputs: Vec<i64>) -> Vec<i64> {
+        let mut output = vec![];
+        Machine::new(&program)
+            .run(&mut VecDeque::from(inputs), &mut output)
+            .unwrap();
+        output
+    }
+
+    fn error(program: Vec<i64>) -> IntcodeError {
+        let mut machine = Machine::new(&program);
+        let error = machine.run(&mut VecDeque::new(), &mut vec![]).unwrap_err();
+        assert_eq!(machine.ip(), error.address());
+        error
+    }
+
+    #[test]
+    fn test_add_operation() {
+        assert_eq!(
+            run(vec![1, 5, 6, 7, 99, 1, 2, 0]),
+            vec![1, 5, 6, 7, 99, 1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn test_mul_operation() {
+        assert_eq!(
+            run(vec![2, 5, 6, 7, 99, 4, 2, 0]),
+            vec![2, 5, 6, 7, 99, 4, 2, 8]
+        );
+    }
+
+    #[test]
+    fn test_decode() {
+        let instruction = Instruction::decode(&Memory::from(&[1002, 1, 2, 3][..]), 0).unwrap();
+        assert_eq!(instruction.operation, Operation::Mul);
+        assert_eq!(
+            instruction.parameters,
+            vec![
+                Parameter {
+                    mode: Mode::Position,
+                    value: 1
+                },
+                Parameter {
+                    mode: Mode::Immediate,
+                    value: 2
+                },
+                Parameter {
+                    mode: Mode::Position,
+                    value: 3
+                },
+            ]
+        );
+        assert_eq!(instruction.size(), 4);
+    }
+
+    #[test]
+    fn test_parameter_modes() {
+        assert_eq!(run(vec![1002, 4, 3, 4, 33]), vec![1002, 4, 3, 4, 99]);
+        assert_eq!(run(vec![1101, 100, -1, 4, 0]), vec![1101, 100, -1, 4, 99]);
+    }
+
+    #[test]
+    fn test_relative_mode() {
+        assert_eq!(
+            outputs(vec![109, 5, 204, 2, 99, 0, 0, 42], vec![]),
+            vec![42]
+        );
+        assert_eq!(
+            run(vec![109, 7, 21101, 1, 2, 0, 99, 0]),
+            vec![109, 7, 21101, 1, 2, 0, 99, 3]
+        );
+        assert_eq!(
+            outputs(vec![109, 3, 203, 5, 4, 8, 99, 0, 0], vec![12]),
+            vec![12]
+        );
+        assert_eq!(outputs(vec![109, -1, 204, 1, 99], vec![]), vec![109]);
+    }
+
+    #[test]
+    fn test_memory_past_program() {
+        assert_eq!(
+            run(vec![1101, 1, 2, 7, 99]),
+            vec![1101, 1, 2, 7, 99, 0, 0, 3]
+        );
+        assert_eq!(
+            outputs(vec![1101, 6, 7, 5000000, 4, 5000000, 99], vec![]),
+            vec![13]
+        );
+        assert_eq!(outputs(vec![4, 100, 99], vec![]), vec![0]);
+    }
+
+    #[test]
+    fn test_quine() {
+        let program = vec![
+            109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+        ];
+        assert_eq!(outputs(program.clone(), vec![]), program);
+    }
+
+    #[test]
+    fn test_jumps() {
+        // jumps over the instruction at address 3 which would write 1 to address 8
+        assert_eq!(
+            run(vec![1105, 1, 7, 1101, 0, 1, 8, 99, 0]),
+            vec![1105, 1, 7, 1101, 0, 1, 8, 99, 0]
+        );
+        assert_eq!(
+            run(vec![1106, 1, 7, 1101, 0, 1, 8, 99, 0]),
+            vec![1106, 1, 7, 1101, 0, 1, 8, 99, 1]
+        );
+    }
+
+    #[test]
+    fn test_comparisons() {
+        assert_eq!(run(vec![1107, 1, 2, 5, 99, 7]), vec![1107, 1, 2, 5, 99, 1]);
+        assert_eq!(run(vec![1108, 1, 2, 5, 99, 7]), vec![1108, 1, 2, 5, 99, 0]);
+    }
+
+    #[test]
+    fn test_input_output() {
+        assert_eq!(outputs(vec![3, 0, 4, 0, 99], vec![42]), vec![42]);
+        assert_eq!(
+            outputs(vec![3, 0, 3, 1, 4, 1, 4, 0, 99], vec![1, 2]),
+            vec![2, 1]
+        );
+    }
+
+    #[test]
+    fn test_missing_input() {
+        assert_eq!(
+            error(vec![1101, 0, 0, 0, 3, 0, 99]),
+            IntcodeError::MissingInput { address: 4 }
+        );
+    }
+
+    #[test]
+    fn test_non_blocking_input() {
+        let mut input = NonBlockingInput::new(-1);
+        input.push(4);
+        let mut output: Vec<i64> = vec![];
+        let mut machine = Machine::new(&[3, 9, 4, 9, 3, 9, 4, 9, 99, 0]);
+        machine.run(&mut input, &mut output).unwrap();
+        assert_eq!(output, vec![4, -1]);
+        assert_eq!(input.empty_reads(), 1);
+    }
+
+    #[test]
+    fn test_run_until_event() {
+        // echoes its inputs forever
+        let mut machine = Machine::new(&[3, 7, 4, 7, 1105, 1, 0, 0]);
+        assert_eq!(machine.run_until_event(), Ok(Event::NeedsInput));
+        assert_eq!(machine.ip(), 0);
+        machine.push_input(5);
+        machine.push_input(6);
+        assert_eq!(machine.run_until_event(), Ok(Event::Output(5)));
+        assert_eq!(machine.run_until_event(), Ok(Event::Output(6)));
+        assert_eq!(machine.run_until_event(), Ok(Event::NeedsInput));
+
+        let mut machine = Machine::new(&[104, 1, 99]);
+        assert_eq!(machine.run_until_event(), Ok(Event::Output(1)));
+        assert_eq!(machine.run_until_event(), Ok(Event::Halted));
+        assert_eq!(machine.run_until_event(), Ok(Event::Halted));
+
+        let mut machine = Machine::new(&[42]);
+        assert!(machine.run_until_event().is_err());
+    }
+
+    #[test]
+    fn test_chained_machines() {
+        // doubles its inputs until it reads 0
+        let doubler = [
+            3, 15, 1006, 15, 14, 1002, 15, 2, 15, 4, 15, 1105, 1, 0, 99, 0,
+        ];
+        let (mut first, mut second) = (Machine::new(&doubler), Machine::new(&doubler));
+        for value in [1, 2, 3, 0] {
+            first.push_input(value);
+        }
+        while let Event::Output(value) = first.run_until_event().unwrap() {
+            second.push_input(value);
+        }
+        second.push_input(0);
+        let mut results = vec![];
+        while let Event::Output(value) = second.run_until_event().unwrap() {
+            results.push(value);
+        }
+        assert_eq!(results, vec![4, 8, 12]);
+        assert!(first.is_halted() && second.is_halted());
+    }
+
+    #[test]
+    fn test_equal_to_8() {
+        let position_mode = vec![3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8];
+        assert_eq!(outputs(position_mode.clone(), vec![8]), vec![1]);
+        assert_eq!(outputs(position_mode, vec![7]), vec![0]);
+        let immediate_mode = vec![3, 3, 1108, -1, 8, 3, 4, 3, 99];
+        assert_eq!(outputs(immediate_mode.clone(), vec![8]), vec![1]);
+        assert_eq!(outputs(immediate_mode, vec![9]), vec![0]);
+    }
+
+    #[test]
+    fn test_machine_run() {
+        assert_eq!(run(vec![1, 0, 0, 0, 99]), vec![2, 0, 0, 0, 99]);
+        assert_eq!(run(vec![2, 3, 0, 3, 99]), vec![2, 3, 0, 6, 99]);
+        assert_eq!(run(vec![2, 4, 4, 5, 99, 0]), vec![2, 4, 4, 5, 99, 9801]);
+        assert_eq!(
+            run(vec![1, 1, 1, 4, 99, 5, 6, 0, 99]),
+            vec![30, 1, 1, 4, 2, 5, 6, 0, 99]
+        );
+    }
+
+    #[test]
+    fn test_large_numbers() {
+        assert_eq!(
+            outputs(vec![104, 1125899906842624, 99], vec![]),
+            vec![1125899906842624]
+        );
+        assert_eq!(
+            outputs(vec![1102, 34915192, 34915192, 7, 4, 7, 99, 0], vec![]),
+            vec![1219070632396864]
+        );
+    }
+
+    #[test]
+    fn test_overflow() {
+        assert_eq!(
+            error(vec![1102, i64::MAX, 2, 0, 99]),
+            IntcodeError::Overflow { address: 0 }
+        );
+        assert_eq!(
+            error(vec![109, i64::MAX, 109, 1, 99]),
+            IntcodeError::Overflow { address: 2 }
+        );
+    }
+
+    #[test]
+    fn test_unknown_opcode() {
+        assert_eq!(
+            error(vec![1101, 0, 0, 0, 42]),
+            IntcodeError::UnknownOpcode {
+                address: 4,
+                opcode: 42
+            }
+        );
+        assert_eq!(
+            error(vec![-1]),
+            IntcodeError::UnknownOpcode {
+                address: 0,
+                opcode: -1
+            }
+        );
+    }
+
+    #[test]
+    fn test_invalid_parameter_mode() {
+        assert_eq!(
+            error(vec![30001, 0, 0, 0, 99]),
+            IntcodeError::InvalidParameterMode {
+                address: 0,
+                parameter: 2,
+                mode: 3
+            }
+        );
+    }
+
+    #[test]
+    fn test_negative_address() {
+        assert_eq!(
+            error(vec![1, -1, 0, 0, 99]),
+            IntcodeError::NegativeAddress {
+                address: 0,
+                target: -1
+            }
+        );
+        assert_eq!(
+            error(vec![109, -5, 204, 1, 99]),
+            IntcodeError::NegativeAddress {
+                address: 2,
+                target: -4
+            }
+        );
+        assert_eq!(
+            error(vec![1105, 1, -3, 99]),
+            IntcodeError::NegativeAddress {
+                address: 0,
+                target: -3
+            }
+        );
+    }
+
+    #[test]
+    fn test_step_budget() {
+        // JT #1, #0
+        let mut machine = Machine::new(&[1105, 1, 0]);
+        machine.limit_steps(10);
+        assert_eq!(
+            machine.run(&mut VecDeque::new(), &mut vec![]),
+            Err(IntcodeError::StepBudgetExceeded {
+                address: 0,
+                budget: 10
+            })
+        );
+        assert_eq!(machine.steps(), 10);
+    }
+
+    #[test]
+    fn test_write_in_immediate_mode() {
+        assert_eq!(
+            error(vec![11101, 1, 1, 0, 99]),
+            IntcodeError::WriteInImmediateMode {
+                address: 0,
+                parameter: 2
+            }
+        );
+    }
+
+    #[test]
+    fn test_detect_loops() {
+        let mut machine = Machine::new(&[1105, 1, 0]);
+        machine.detect_loops();
+        let error = machine.run(&mut VecDeque::new(), &mut vec![]).unwrap_err();
+        assert_eq!(
+            error,
+            IntcodeError::InfiniteLoop {
+                address: 0,
+                addresses: 0..3,
+                period: 1
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "infinite loop of 1 steps over addresses 0..3 detected at address 0"
+        );
+
+        // A counter going to 1000 changes the memory at every iteration, it does not loop.
+        let program = assembler::assemble(
+            "
+            loop: ADD [count], #1, [count]
+                  EQ  [count], #1000, [done]
+                  JF  [done], #loop
+                  HLT
+            count: DATA 0
+            done:  DATA 0
+            ",
+        )
+        .unwrap();
+        let mut machine = Machine::new(&program);
+        machine.detect_loops();
+        assert_eq!(machine.run(&mut VecDeque::new(), &mut vec![]), Ok(()));
+        assert_eq!(machine.steps(), 3001);
+
+        // Reading inputs in a loop may go on until the inputs run out.
+        let mut machine = Machine::new(&[3, 3, 1105, 1, 0]);
+        machine.detect_loops();
+        let mut inputs = VecDeque::from(vec![1; 20]);
+        assert_eq!(
+            machine.run(&mut inputs, &mut vec![]),
+            Err(IntcodeError::MissingInput { address: 0 })
+        );
+    }
+
+    #[test]
+    fn test_error_display() {
+        assert_eq!(
+            format!("{}", error(vec![1101, 0, 0, 0, 42])),
+            "unknown opcode 42 at address 4"
+        );
+    }
+
+    #[test]
+    fn test_word_types() {
+        let mut output: Vec<i32> = vec![];
+        Machine::new(&[1102, 3, 4, 7, 4, 7, 99, 0])
+            .run(&mut VecDeque::new(), &mut output)
+            .unwrap();
+        assert_eq!(output, vec![12]);
+        let mut output: Vec<i128> = vec![];
+        Machine::new(&[1102, i128::from(i64::MAX), 4, 7, 4, 7, 99, 0])
+            .run(&mut VecDeque::new(), &mut output)
+            .unwrap();
+        assert_eq!(output, vec![i128::from(i64::MAX) * 4]);
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn test_bigint_words() {
+        use num_bigint::BigInt;
+        let program: Vec<BigInt> =
+            parse_program("1102,9223372036854775807,9223372036854775807,7,4,7,99,0").unwrap();
+        let mut output: Vec<BigInt> = vec![];
+        Machine::new(&program)
+            .run(&mut VecDeque::new(), &mut output)
+            .unwrap();
+        assert_eq!(output, vec![BigInt::from(i64::MAX) * i64::MAX]);
+    }
+
+    #[test]
+    fn test_parse_program() {
+        assert_eq!(parse_program("1,0,0,3,99\n"), Ok(vec![1, 0, 0, 3, 99]));
+        assert!(parse_program::<i64>("1,a").is_err());
+    }
+}
@@ -0,0 +1,217 @@
+// Chains of amplifiers: copies of an Intcode program each started with a phase setting, every
+// amplifier reading the signal output by the previous one. The first amplifier gets a 0 signal.
+use super::compiled::Compiled;
+use super::{Event, IntcodeError, Machine, Word};
+use std::error::Error;
+use std::fmt::{self, Debug, Display};
+use std::sync::Arc;
+
+/// STEP_BUDGET is the number of instructions each amplifier may execute, so amplifiers which
+/// never halt fail with StepBudgetExceeded instead of running forever.
+pub const STEP_BUDGET: usize = 10_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wiring {
+    /// The signal goes once through every amplifier.
+    Series,
+    /// The output of the last amplifier is fed back into the first one, until they halt.
+    FeedbackLoop,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AmplifierError<W = i64> {
+    /// The amplifier halted or waited for an input instead of outputting a signal.
+    NoOutput { amplifier: usize },
+    Intcode {
+        amplifier: usize,
+        error: IntcodeError<W>,
+    },
+}
+
+impl<W: Display> Display for AmplifierError<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AmplifierError::NoOutput { amplifier } => {
+                write!(f, "amplifier {} stopped without output", amplifier)
+            }
+            AmplifierError::Intcode { amplifier, error } => {
+                write!(f, "amplifier {} failed: {}", amplifier, error)
+            }
+        }
+    }
+}
+
+impl<W: Debug + Display> Error for AmplifierError<W> {}
+
+/// amplify runs a chain of amplifiers, one per phase setting, and returns the last signal output
+/// by the last amplifier.
+pub fn amplify<W: Word>(
+    program: &[W],
+    phases: &[W],
+    wiring: Wiring,
+) -> Result<W, AmplifierError<W>> {
+    run_chain(
+        &Arc::new(Compiled::new(program)),
+        phases,
+        wiring,
+        STEP_BUDGET,
+    )
+}
+
+fn run_chain<W: Word>(
+    compiled: &Arc<Compiled<W>>,
+    phases: &[W],
+    wiring: Wiring,
+    budget: usize,
+) -> Result<W, AmplifierError<W>> {
+    let mut amplifiers: Vec<Machine<W>> = phases
+        .iter()
+        .map(|phase| {
+            let mut machine = Machine::from_compiled(Arc::clone(compiled));
+            machine.limit_steps(budget);
+            machine.push_input(phase.clone());
+            machine
+        })
+        .collect();
+    let mut signal = W::zero();
+    let mut thrusters: Option<W> = None;
+    loop {
+        for (i, amplifier) in amplifiers.iter_mut().enumerate() {
+            amplifier.push_input(signal);
+            let event = amplifier
+                .run_until_event()
+                .map_err(|error| AmplifierError::Intcode {
+                    amplifier: i,
+                    error,
+                })?;
+            signal = match event {
+                Event::Output(value) => value,
+                Event::Halted if wiring == Wiring::FeedbackLoop => {
+                    return thrusters.ok_or(AmplifierError::NoOutput { amplifier: i })
+                }
+                Event::Halted | Event::NeedsInput => {
+                    return Err(AmplifierError::NoOutput { amplifier: i })
+                }
+            };
+        }
+        if wiring == Wiring::Series {
+            return Ok(signal);
+        }
+        thrusters = Some(signal.clone());
+    }
+}
+
+/// best_phases tries every ordering of the phase settings and returns the highest signal with the
+/// ordering producing it.
+pub fn best_phases<W: Word>(
+    program: &[W],
+    phases: &[W],
+    wiring: Wiring,
+) -> Result<(W, Vec<W>), AmplifierError<W>> {
+    let compiled = Arc::new(Compiled::new(program));
+    let mut best: Option<(W, Vec<W>)> = None;
+    for permutation in permutations(phases) {
+        let signal = run_chain(&compiled, &permutation, wiring, STEP_BUDGET)?;
+        let better = match &best {
+            Some((highest, _)) => signal > *highest,
+            None => true,
+        };
+        if better {
+            best = Some((signal, permutation));
+        }
+    }
+    // There is always at least one permutation, the empty one when there are no phases.
+    Ok(best.unwrap())
+}
+
+/// permutations returns every ordering of `items`.
+fn permutations<T: Clone>(items: &[T]) -> Vec<Vec<T>> {
+    if items.is_empty() {
+        return vec![vec![]];
+    }
+    let mut result: Vec<Vec<T>> = vec![];
+    for i in 0..items.len() {
+        let mut rest = items.to_vec();
+        let item = rest.remove(i);
+        for mut permutation in permutations(&rest) {
+            permutation.insert(0, item.clone());
+            result.push(permutation);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SERIES_PROGRAM: [i64; 17] = [
+        3, 15, 3, 16, 1002, 16, 10, 16, 1, 16, 15, 15, 4, 15, 99, 0, 0,
+    ];
+    const FEEDBACK_PROGRAM: [i64; 29] = [
+        3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28, -1, 28,
+        1005, 28, 6, 99, 0, 0, 5,
+    ];
+
+    #[test]
+    fn test_permutations() {
+        assert_eq!(permutations::<i64>(&[]), vec![Vec::<i64>::new()]);
+        assert_eq!(
+            permutations(&[1, 2, 3]),
+            vec![
+                vec![1, 2, 3],
+                vec![1, 3, 2],
+                vec![2, 1, 3],
+                vec![2, 3, 1],
+                vec![3, 1, 2],
+                vec![3, 2, 1]
+            ]
+        );
+    }
+
+    #[test]
+    fn test_series() {
+        assert_eq!(
+            amplify(&SERIES_PROGRAM, &[4, 3, 2, 1, 0], Wiring::Series),
+            Ok(43210)
+        );
+        assert_eq!(
+            best_phases(&SERIES_PROGRAM, &[0, 1, 2, 3, 4], Wiring::Series),
+            Ok((43210, vec![4, 3, 2, 1, 0]))
+        );
+    }
+
+    #[test]
+    fn test_feedback_loop() {
+        assert_eq!(
+            amplify(&FEEDBACK_PROGRAM, &[9, 8, 7, 6, 5], Wiring::FeedbackLoop),
+            Ok(139_629_729)
+        );
+        assert_eq!(
+            best_phases(&FEEDBACK_PROGRAM, &[5, 6, 7, 8, 9], Wiring::FeedbackLoop),
+            Ok((139_629_729, vec![9, 8, 7, 6, 5]))
+        );
+    }
+
+    #[test]
+    fn test_amplifier_without_output() {
+        assert_eq!(amplify(&SERIES_PROGRAM, &[0, 1], Wiring::Series), Ok(1));
+        assert_eq!(
+            amplify(&[3, 0, 99], &[0, 1], Wiring::Series),
+            Err(AmplifierError::NoOutput { amplifier: 0 })
+        );
+    }
+
+    #[test]
+    fn test_amplifiers_never_halting() {
+        // Every amplifier outputs what it reads, forever.
+        let echo = Arc::new(Compiled::new(&[3, 7, 4, 7, 1105, 1, 0, 0]));
+        match run_chain(&echo, &[0, 1], Wiring::FeedbackLoop, 1000) {
+            Err(AmplifierError::Intcode {
+                error: IntcodeError::StepBudgetExceeded { budget: 1000, .. },
+                ..
+            }) => (),
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+}
@@ -0,0 +1,248 @@
+// Static analysis of Intcode programs: basic blocks, control-flow graph and reachability.
+//
+// Like the disassembler, the analysis only follows the jumps with an immediate target. A block
+// ending with a jump whose target is computed at runtime is marked as indirect.
+use super::disassembler::{reachable_instructions, successors};
+use super::{Instruction, Memory, Mode, Operation, Word};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::ops::Range;
+
+/// BasicBlock is a sequence of instructions always executed together, entered only by its first
+/// instruction and left only by its last one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BasicBlock<W = i64> {
+    pub start: usize,
+    pub instructions: Vec<(usize, Instruction<W>)>,
+    /// The starts of the blocks execution can continue at.
+    pub successors: Vec<usize>,
+    /// Whether the block can also jump to an address computed at runtime.
+    pub indirect: bool,
+}
+
+impl<W: Word> BasicBlock<W> {
+    /// end returns the address following the last instruction of the block.
+    pub fn end(&self) -> usize {
+        self.instructions
+            .last()
+            .map_or(self.start, |(address, instruction)| {
+                address + instruction.size()
+            })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControlFlowGraph<W = i64> {
+    pub blocks: BTreeMap<usize, BasicBlock<W>>,
+    /// The cells no reachable instruction is made of: unreachable code or data.
+    pub unreachable: Vec<Range<usize>>,
+}
+
+/// is_control_transfer tells if execution may not continue right after the instruction.
+fn is_control_transfer<W: Word>(instruction: &Instruction<W>) -> bool {
+    matches!(
+        instruction.operation,
+        Operation::JumpIfTrue | Operation::JumpIfFalse | Operation::Halt
+    )
+}
+
+/// is_indirect tells if the instruction may jump to an address computed at runtime.
+fn is_indirect<W: Word>(instruction: &Instruction<W>) -> bool {
+    let jump_if_zero = match instruction.operation {
+        Operation::JumpIfTrue => false,
+        Operation::JumpIfFalse => true,
+        _ => return false,
+    };
+    let (condition, target) = (&instruction.parameters[0], &instruction.parameters[1]);
+    let never_taken =
+        condition.mode == Mode::Immediate && condition.value.is_zero() != jump_if_zero;
+    target.mode != Mode::Immediate && !never_taken
+}
+
+impl<W: Word> ControlFlowGraph<W> {
+    pub fn new(program: &[W]) -> Self {
+        let memory = Memory::from(program);
+        let instructions = reachable_instructions(&memory);
+
+        let mut leaders: BTreeSet<usize> = BTreeSet::new();
+        if instructions.contains_key(&0) {
+            leaders.insert(0);
+        }
+        for (address, instruction) in &instructions {
+            if is_control_transfer(instruction) {
+                leaders.extend(successors(instruction, *address));
+                leaders.insert(address + instruction.size());
+            }
+        }
+        leaders.retain(|leader| instructions.contains_key(leader));
+
+        let mut blocks: BTreeMap<usize, BasicBlock<W>> = BTreeMap::new();
+        for leader in &leaders {
+            let mut block = BasicBlock {
+                start: *leader,
+                instructions: vec![],
+                successors: vec![],
+                indirect: false,
+            };
+            let mut address = *leader;
+            while let Some(instruction) = instructions.get(&address) {
+                block.instructions.push((address, instruction.clone()));
+                let next = address + instruction.size();
+                if is_control_transfer(instruction) || leaders.contains(&next) {
+                    block.successors = successors(instruction, address)
+                        .into_iter()
+                        .filter(|successor| leaders.contains(successor))
+                        .collect();
+                    block.indirect = is_indirect(instruction);
+                    break;
+                }
+                address = next;
+            }
+            blocks.insert(*leader, block);
+        }
+
+        let covered: BTreeSet<usize> = instructions
+            .iter()
+            .flat_map(|(address, instruction)| *address..address + instruction.size())
+            .collect();
+        let mut unreachable: Vec<Range<usize>> = vec![];
+        for address in (0..program.len()).filter(|a| !covered.contains(a)) {
+            match unreachable.last_mut() {
+                Some(range) if range.end == address => range.end += 1,
+                _ => unreachable.push(address..address + 1),
+            }
+        }
+        ControlFlowGraph {
+            blocks,
+            unreachable,
+        }
+    }
+
+    /// block_at returns the block holding the instruction starting at `address`.
+    pub fn block_at(&self, address: usize) -> Option<&BasicBlock<W>> {
+        self.blocks
+            .range(..=address)
+            .next_back()
+            .map(|(_, block)| block)
+            .filter(|block| block.instructions.iter().any(|(a, _)| *a == address))
+    }
+
+    pub fn is_reachable(&self, address: usize) -> bool {
+        self.block_at(address).is_some()
+    }
+
+    /// to_dot exports the graph in the Graphviz DOT language, one node per block.
+    pub fn to_dot(&self) -> String {
+        let mut dot =
+            String::from("digraph intcode {\n    node [shape=box, fontname=monospace];\n");
+        for block in self.blocks.values() {
+            let label: String = block
+                .instructions
+                .iter()
+                .map(|(address, instruction)| format!("{}: {}\\l", address, instruction))
+                .collect();
+            writeln!(dot, "    b{} [label=\"{}\"];", block.start, label).unwrap();
+        }
+        for block in self.blocks.values() {
+            for successor in &block.successors {
+                writeln!(dot, "    b{} -> b{};", block.start, successor).unwrap();
+            }
+        }
+        let indirect: Vec<&BasicBlock<W>> = self
+            .blocks
+            .values()
+            .filter(|block| block.indirect)
+            .collect();
+        if !indirect.is_empty() {
+            dot.push_str("    indirect [label=\"?\", shape=circle];\n");
+            for block in indirect {
+                writeln!(dot, "    b{} -> indirect [style=dashed];", block.start).unwrap();
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::assembler::assemble;
+    use crate::intcode::parse_program;
+
+    fn countdown() -> Vec<i64> {
+        assemble(
+            "
+                   IN  [value]
+            loop:  OUT [value]
+                   ADD [value], #-1, [value]
+                   JT  [value], #loop
+                   HLT
+                   DATA 42
+            value: DATA 0
+            ",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_basic_blocks() {
+        let graph = ControlFlowGraph::new(&countdown());
+        let starts: Vec<usize> = graph.blocks.keys().copied().collect();
+        assert_eq!(starts, vec![0, 2, 11]);
+        assert_eq!(graph.blocks[&0].successors, vec![2]);
+        assert_eq!(graph.blocks[&2].end(), 11);
+        assert_eq!(graph.blocks[&2].successors, vec![11, 2]);
+        assert_eq!(graph.blocks[&11].successors, Vec::<usize>::new());
+        assert_eq!(graph.unreachable, vec![12..14]);
+        assert_eq!(graph.block_at(4).map(|block| block.start), Some(2));
+        assert!(!graph.is_reachable(12));
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let graph = ControlFlowGraph::new(&countdown());
+        assert_eq!(
+            graph.to_dot(),
+            "digraph intcode {\n\
+             \x20   node [shape=box, fontname=monospace];\n\
+             \x20   b0 [label=\"0: IN  [13]\\l\"];\n\
+             \x20   b2 [label=\"2: OUT [13]\\l4: ADD [13], #-1, [13]\\l8: JT  [13], #2\\l\"];\n\
+             \x20   b11 [label=\"11: HLT\\l\"];\n\
+             \x20   b0 -> b2;\n\
+             \x20   b2 -> b11;\n\
+             \x20   b2 -> b2;\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn test_indirect_jump() {
+        // JT #1, [3] jumps to the address stored in the cell 3.
+        let graph = ControlFlowGraph::new(&[1106, 0, 4, 99, 105, 1, 3]);
+        assert!(!graph.blocks[&0].indirect);
+        assert!(graph.blocks[&4].indirect);
+        assert!(graph.to_dot().contains("b4 -> indirect [style=dashed];"));
+    }
+
+    #[test]
+    fn test_day5_graph() {
+        let mut program: Vec<i64> =
+            parse_program(include_str!("../../input/2019/day5.txt")).unwrap();
+        // The opcode at 6 is only valid once patched by the instruction at 2.
+        let graph = ControlFlowGraph::new(&program);
+        assert_eq!(graph.blocks.len(), 1);
+        assert_eq!(graph.unreachable, vec![6..program.len()]);
+        // With the input 5 of the second part, it jumps to the tests starting at 238.
+        program[6] = 1105;
+        let graph = ControlFlowGraph::new(&program);
+        assert!(graph.blocks.len() > 1);
+        assert!(graph.is_reachable(238));
+        assert!(!graph.is_reachable(10));
+        for block in graph.blocks.values() {
+            for successor in &block.successors {
+                assert!(graph.blocks.contains_key(successor));
+            }
+        }
+    }
+}
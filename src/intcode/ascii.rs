@@ -0,0 +1,200 @@
+// ASCII adapter for Intcode programs speaking text: they read newline-terminated commands one
+// character code at a time and print character codes. Output values which are not ASCII codes are
+// results, passed through as numbers.
+use super::{Event, IntcodeError, Machine};
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+const LAST_ASCII_CODE: i64 = 127;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AsciiOutput {
+    /// A line of text, without its newline.
+    Line(String),
+    /// Text output without a newline before the program waited for a command or halted.
+    Prompt(String),
+    Value(i64),
+}
+
+impl fmt::Display for AsciiOutput {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AsciiOutput::Line(text) | AsciiOutput::Prompt(text) => write!(f, "{}", text),
+            AsciiOutput::Value(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// encode returns the input codes of a command, its newline included.
+pub fn encode(command: &str) -> Vec<i64> {
+    command
+        .chars()
+        .chain(std::iter::once('\n'))
+        .map(|c| c as i64)
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+pub struct AsciiMachine {
+    machine: Machine,
+    /// The characters output since the last newline.
+    line: String,
+}
+
+impl AsciiMachine {
+    pub fn new(program: &[i64]) -> Self {
+        AsciiMachine {
+            machine: Machine::new(program),
+            line: String::new(),
+        }
+    }
+
+    pub fn machine(&self) -> &Machine {
+        &self.machine
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.machine.is_halted()
+    }
+
+    /// send queues a command for the program.
+    pub fn send(&mut self, command: &str) {
+        for code in encode(command) {
+            self.machine.push_input(code);
+        }
+    }
+
+    /// run executes the program until it halts or waits for a command, and returns what it
+    /// output.
+    pub fn run(&mut self) -> Result<Vec<AsciiOutput>, IntcodeError> {
+        let mut outputs: Vec<AsciiOutput> = vec![];
+        loop {
+            match self.machine.run_until_event()? {
+                Event::Output(code) if code == i64::from(b'\n') => {
+                    outputs.push(AsciiOutput::Line(self.line.split_off(0)));
+                }
+                Event::Output(code) if (0..=LAST_ASCII_CODE).contains(&code) => {
+                    self.line.push(code as u8 as char);
+                }
+                Event::Output(value) => outputs.push(AsciiOutput::Value(value)),
+                Event::NeedsInput | Event::Halted => {
+                    if !self.line.is_empty() {
+                        outputs.push(AsciiOutput::Prompt(self.line.split_off(0)));
+                    }
+                    return Ok(outputs);
+                }
+            }
+        }
+    }
+
+    /// run_script sends every command of `commands` once the program waits for one, and returns
+    /// everything the program output.
+    pub fn run_script(&mut self, commands: &[&str]) -> Result<Vec<AsciiOutput>, IntcodeError> {
+        let mut outputs = self.run()?;
+        for command in commands {
+            if self.is_halted() {
+                break;
+            }
+            self.send(command);
+            outputs.extend(self.run()?);
+        }
+        Ok(outputs)
+    }
+
+    /// play lets the program talk with a terminal: its outputs are printed to `output` and the
+    /// lines of `input` are sent as commands, until the program halts or the input ends.
+    pub fn play<R: BufRead, O: Write>(&mut self, input: R, mut output: O) -> io::Result<()> {
+        let mut lines = input.lines();
+        loop {
+            let outputs = self
+                .run()
+                .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+            for text in outputs {
+                match text {
+                    AsciiOutput::Prompt(prompt) => write!(output, "{}", prompt)?,
+                    text => writeln!(output, "{}", text)?,
+                }
+            }
+            output.flush()?;
+            if self.is_halted() {
+                return Ok(());
+            }
+            match lines.next() {
+                Some(line) => self.send(&line?),
+                None => return Ok(()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::assembler::assemble;
+
+    /// shout prompts for a word and prints it back followed by its length times 1000, until it
+    /// reads an empty line.
+    fn shout() -> Vec<i64> {
+        assemble(
+            "
+            prompt: OUT #62
+                    ADD #0, #0, [length]
+            read:   IN  [c]
+                    EQ  [c], #10, [end]
+                    JT  [end], #done
+                    OUT [c]
+                    ADD [length], #1, [length]
+                    JT  #1, #read
+            done:   JF  [length], #quit
+                    OUT #10
+                    MUL [length], #1000, [length]
+                    OUT [length]
+                    JT  #1, #prompt
+            quit:   OUT #98
+                    OUT #121
+                    OUT #101
+                    OUT #10
+                    OUT #1000
+                    HLT
+            c:      DATA 0
+            end:    DATA 0
+            length: DATA 0
+            ",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(encode("NOT A J"), vec![78, 79, 84, 32, 65, 32, 74, 10]);
+    }
+
+    #[test]
+    fn test_run_script() {
+        let mut machine = AsciiMachine::new(&shout());
+        let outputs = machine.run_script(&["hey", "", "ignored"]).unwrap();
+        assert_eq!(
+            outputs,
+            vec![
+                AsciiOutput::Prompt(String::from(">")),
+                AsciiOutput::Line(String::from("hey")),
+                AsciiOutput::Value(3000),
+                AsciiOutput::Prompt(String::from(">")),
+                AsciiOutput::Line(String::from("bye")),
+                AsciiOutput::Value(1000),
+            ]
+        );
+        assert!(machine.is_halted());
+    }
+
+    #[test]
+    fn test_play() {
+        let mut machine = AsciiMachine::new(&shout());
+        let mut transcript: Vec<u8> = vec![];
+        machine.play("ho\n\n".as_bytes(), &mut transcript).unwrap();
+        assert_eq!(
+            String::from_utf8(transcript).unwrap(),
+            ">ho\n2000\n>bye\n1000\n"
+        );
+    }
+}
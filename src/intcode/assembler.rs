@@ -0,0 +1,382 @@
+// Assembler turning Intcode assembly into program memory.
+//
+// It reads the syntax of the disassembler listings, one statement per line:
+//
+//     ; comments start with a semicolon
+//     start: IN  [value]             ; `label:` names the address of the next word
+//            JF  [value], #end       ; `[a]` position, `#a` immediate, `[rb+a]` relative mode
+//            MUL [value], #2, [value]
+//            OUT [value]
+//            JT  #1, #start
+//     end:   HLT
+//     value: DATA 0                  ; raw words, separated by commas
+//
+// Operands are numbers, labels or a label with an offset (`value+1`). Mnemonics are case
+// insensitive and a line may start with its address (`   12: HLT`), which is checked.
+use super::{Mode, Operation};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+const DATA_DIRECTIVE: &str = "DATA";
+const RELATIVE_BASE: &str = "rb";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AssemblyErrorKind {
+    UnknownMnemonic(String),
+    WrongOperandCount {
+        expected: usize,
+        found: usize,
+    },
+    InvalidOperand(String),
+    InvalidLabel(String),
+    UndefinedLabel(String),
+    DuplicateLabel(String),
+    WriteInImmediateMode(String),
+    /// The address a line starts with is not where its statement is assembled.
+    AddressMismatch {
+        expected: usize,
+        found: usize,
+    },
+}
+
+/// AssemblyError is an error on the line `line` (starting at 1) of the source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssemblyError {
+    pub line: usize,
+    pub kind: AssemblyErrorKind,
+}
+
+impl fmt::Display for AssemblyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        match &self.kind {
+            AssemblyErrorKind::UnknownMnemonic(mnemonic) => {
+                write!(f, "unknown mnemonic {}", mnemonic)
+            }
+            AssemblyErrorKind::WrongOperandCount { expected, found } => {
+                write!(f, "expected {} operands, found {}", expected, found)
+            }
+            AssemblyErrorKind::InvalidOperand(operand) => write!(f, "invalid operand {}", operand),
+            AssemblyErrorKind::InvalidLabel(label) => write!(f, "invalid label {}", label),
+            AssemblyErrorKind::UndefinedLabel(label) => write!(f, "undefined label {}", label),
+            AssemblyErrorKind::DuplicateLabel(label) => write!(f, "label {} defined twice", label),
+            AssemblyErrorKind::WriteInImmediateMode(operand) => {
+                write!(
+                    f,
+                    "operand {} is written to and cannot be immediate",
+                    operand
+                )
+            }
+            AssemblyErrorKind::AddressMismatch { expected, found } => {
+                write!(f, "statement is at address {}, not {}", expected, found)
+            }
+        }
+    }
+}
+
+impl Error for AssemblyError {}
+
+/// Value is a number or an address given by a label, plus an offset.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Value {
+    Number(i64),
+    Label(String, i64),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Operand {
+    mode: Mode,
+    value: Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Statement {
+    Instruction(Operation, Vec<Operand>),
+    Data(Vec<Value>),
+}
+
+impl Statement {
+    fn size(&self) -> usize {
+        match self {
+            Statement::Instruction(_, operands) => operands.len() + 1,
+            Statement::Data(values) => values.len(),
+        }
+    }
+}
+
+fn is_label(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            name != RELATIVE_BASE && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    }
+}
+
+fn parse_value(input: &str) -> Option<Value> {
+    let input = input.trim();
+    if let Ok(number) = input.parse() {
+        return Some(Value::Number(number));
+    }
+    let (label, offset) = match input.rfind(['+', '-']) {
+        Some(index) => {
+            let offset: i64 = input[index + 1..].trim().parse().ok()?;
+            let sign = if &input[index..=index] == "-" { -1 } else { 1 };
+            (input[..index].trim(), sign * offset)
+        }
+        None => (input, 0),
+    };
+    if is_label(label) {
+        Some(Value::Label(String::from(label), offset))
+    } else {
+        None
+    }
+}
+
+fn parse_operand(input: &str) -> Option<Operand> {
+    let input = input.trim();
+    if let Some(value) = input.strip_prefix('#') {
+        return Some(Operand {
+            mode: Mode::Immediate,
+            value: parse_value(value)?,
+        });
+    }
+    let inner = input.strip_prefix('[')?.strip_suffix(']')?.trim();
+    match inner.strip_prefix(RELATIVE_BASE) {
+        Some(offset) if !is_label(inner) => {
+            let offset = offset.trim();
+            let value = match offset.strip_prefix('+') {
+                Some(positive) => parse_value(positive)?,
+                None if offset.is_empty() => Value::Number(0),
+                None if offset.starts_with('-') => parse_value(offset)?,
+                None => return None,
+            };
+            Some(Operand {
+                mode: Mode::Relative,
+                value,
+            })
+        }
+        _ => Some(Operand {
+            mode: Mode::Position,
+            value: parse_value(inner)?,
+        }),
+    }
+}
+
+fn parse_statement(input: &str) -> Result<Statement, AssemblyErrorKind> {
+    let (mnemonic, rest) = match input.find(char::is_whitespace) {
+        Some(index) => (&input[..index], input[index..].trim()),
+        None => (input, ""),
+    };
+    let arguments: Vec<&str> = if rest.is_empty() {
+        vec![]
+    } else {
+        rest.split(',').collect()
+    };
+    if mnemonic.eq_ignore_ascii_case(DATA_DIRECTIVE) {
+        let values = arguments
+            .iter()
+            .map(|argument| {
+                parse_value(argument)
+                    .ok_or_else(|| AssemblyErrorKind::InvalidOperand(argument.trim().to_string()))
+            })
+            .collect::<Result<Vec<Value>, AssemblyErrorKind>>()?;
+        return Ok(Statement::Data(values));
+    }
+    let operation = Operation::from_mnemonic(mnemonic)
+        .ok_or_else(|| AssemblyErrorKind::UnknownMnemonic(mnemonic.to_string()))?;
+    if arguments.len() != operation.get_args_expected() {
+        return Err(AssemblyErrorKind::WrongOperandCount {
+            expected: operation.get_args_expected(),
+            found: arguments.len(),
+        });
+    }
+    let mut operands: Vec<Operand> = vec![];
+    for (i, argument) in arguments.iter().enumerate() {
+        let argument = argument.trim();
+        let operand = parse_operand(argument)
+            .ok_or_else(|| AssemblyErrorKind::InvalidOperand(argument.to_string()))?;
+        if operand.mode == Mode::Immediate && operation.is_write_parameter(i) {
+            return Err(AssemblyErrorKind::WriteInImmediateMode(
+                argument.to_string(),
+            ));
+        }
+        operands.push(operand);
+    }
+    Ok(Statement::Instruction(operation, operands))
+}
+
+/// assemble turns assembly source into program memory.
+pub fn assemble(source: &str) -> Result<Vec<i64>, AssemblyError> {
+    let mut labels: HashMap<String, usize> = HashMap::new();
+    let mut statements: Vec<(usize, Statement)> = vec![];
+    let mut address: usize = 0;
+
+    for (index, line) in source.lines().enumerate() {
+        let error = |kind| AssemblyError {
+            line: index + 1,
+            kind,
+        };
+        let mut code = line.split(';').next().unwrap_or("").trim();
+        while let Some(colon) = code.find(':') {
+            let prefix = code[..colon].trim();
+            if let Ok(found) = prefix.parse::<usize>() {
+                if found != address {
+                    return Err(error(AssemblyErrorKind::AddressMismatch {
+                        expected: address,
+                        found,
+                    }));
+                }
+            } else if !is_label(prefix) {
+                return Err(error(AssemblyErrorKind::InvalidLabel(prefix.to_string())));
+            } else if labels.insert(prefix.to_string(), address).is_some() {
+                return Err(error(AssemblyErrorKind::DuplicateLabel(prefix.to_string())));
+            }
+            code = code[colon + 1..].trim();
+        }
+        if code.is_empty() {
+            continue;
+        }
+        let statement = parse_statement(code).map_err(error)?;
+        address += statement.size();
+        statements.push((index + 1, statement));
+    }
+
+    let mut program: Vec<i64> = Vec::with_capacity(address);
+    for (line, statement) in statements {
+        let resolve = |value: &Value| match value {
+            Value::Number(number) => Ok(*number),
+            Value::Label(label, offset) => labels
+                .get(label)
+                .map(|address| *address as i64 + offset)
+                .ok_or_else(|| AssemblyError {
+                    line,
+                    kind: AssemblyErrorKind::UndefinedLabel(label.clone()),
+                }),
+        };
+        match statement {
+            Statement::Instruction(operation, operands) => {
+                let mut opcode = operation.opcode();
+                let mut factor = 100;
+                for operand in operands.iter() {
+                    opcode += operand.mode.digit() * factor;
+                    factor *= 10;
+                }
+                program.push(opcode);
+                for operand in operands.iter() {
+                    program.push(resolve(&operand.value)?);
+                }
+            }
+            Statement::Data(values) => {
+                for value in values.iter() {
+                    program.push(resolve(value)?);
+                }
+            }
+        }
+    }
+    Ok(program)
+}
+
+/// to_source returns a program in the comma separated format of the puzzle inputs.
+pub fn to_source(program: &[i64]) -> String {
+    let words: Vec<String> = program.iter().map(|word| word.to_string()).collect();
+    words.join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::disassembler::listing;
+    use crate::intcode::{parse_program, Machine};
+    use std::collections::VecDeque;
+
+    #[test]
+    fn test_assemble_instructions() {
+        assert_eq!(
+            assemble("mul [4], #3, [4]\nhlt"),
+            Ok(vec![1002, 4, 3, 4, 99])
+        );
+        assert_eq!(
+            assemble("ARB #-3\nOUT [rb+2]\nOUT [rb-1]\nIN [rb]"),
+            Ok(vec![109, -3, 204, 2, 204, -1, 203, 0])
+        );
+    }
+
+    #[test]
+    fn test_assemble_labels_and_data() {
+        let source = "
+            ; doubles its input
+            start: IN  [value]
+                   MUL [value], #2, [value]
+                   OUT [value]
+                   JT  #1, #end
+            end:   HLT
+            value: DATA 0, value+1, value-1
+        ";
+        let program = assemble(source).unwrap();
+        assert_eq!(
+            program,
+            vec![3, 12, 1002, 12, 2, 12, 4, 12, 1105, 1, 11, 99, 0, 13, 11]
+        );
+        let mut output = vec![];
+        Machine::new(&program)
+            .run(&mut VecDeque::from(vec![21]), &mut output)
+            .unwrap();
+        assert_eq!(output, vec![42]);
+        assert_eq!(
+            to_source(&program),
+            "3,12,1002,12,2,12,4,12,1105,1,11,99,0,13,11"
+        );
+    }
+
+    #[test]
+    fn test_assemble_errors() {
+        let kind = |source| assemble(source).unwrap_err().kind;
+        assert_eq!(
+            kind("NOP"),
+            AssemblyErrorKind::UnknownMnemonic(String::from("NOP"))
+        );
+        assert_eq!(
+            kind("ADD #1, #2"),
+            AssemblyErrorKind::WrongOperandCount {
+                expected: 3,
+                found: 2
+            }
+        );
+        assert_eq!(
+            kind("OUT 4"),
+            AssemblyErrorKind::InvalidOperand(String::from("4"))
+        );
+        assert_eq!(
+            kind("OUT [nowhere]"),
+            AssemblyErrorKind::UndefinedLabel(String::from("nowhere"))
+        );
+        assert_eq!(
+            kind("a: HLT\na: HLT"),
+            AssemblyErrorKind::DuplicateLabel(String::from("a"))
+        );
+        assert_eq!(
+            kind("IN #3"),
+            AssemblyErrorKind::WriteInImmediateMode(String::from("#3"))
+        );
+        assert_eq!(
+            kind("HLT\n3: HLT"),
+            AssemblyErrorKind::AddressMismatch {
+                expected: 1,
+                found: 3
+            }
+        );
+        assert_eq!(assemble("HLT\nfoo bar").unwrap_err().line, 2);
+    }
+
+    #[test]
+    fn test_disassembly_round_trip() {
+        let program: Vec<i64> = parse_program(include_str!("../../input/2019/day5.txt")).unwrap();
+        assert_eq!(assemble(&listing(&program)), Ok(program));
+        // ADD cut off by the end of the program.
+        let program: Vec<i64> = vec![1101, 2, 3, 4, 1101, 1];
+        assert_eq!(assemble(&listing(&program)), Ok(program));
+    }
+}
@@ -0,0 +1,334 @@
+// Programs compiled ahead of time to a compact bytecode, to run the same program many times
+// without decoding its instructions again at every step, see Machine::from_compiled.
+//
+// benches/intcode.rs measures compiled runs about 1.5 times faster than interpreted ones on the
+// day2 search, whose runs are a few dozen instructions long, and 3 times faster on day5.
+use super::{Input, Instruction, IntcodeError, Machine, Memory, Mode, Operation, Output, Word};
+use std::sync::Arc;
+
+/// MAX_INSTRUCTION_SIZE is the number of cells of the longest instructions, the ones with three
+/// parameters.
+const MAX_INSTRUCTION_SIZE: usize = 4;
+
+/// Arg is a parameter of a compiled instruction, its address already converted when it has one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Arg<W> {
+    Immediate(W),
+    Position(usize),
+    Relative(i64),
+}
+
+/// Op is a compiled instruction. Its arguments past the ones of the operation are unused.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Op<W> {
+    operation: Operation,
+    args: [Arg<W>; MAX_INSTRUCTION_SIZE - 1],
+    size: usize,
+}
+
+impl<W: Word> Op<W> {
+    /// compile returns the op of an instruction, or None if it could fail in a way the interpreter
+    /// must report: these instructions are left to it.
+    fn compile(instruction: &Instruction<W>) -> Option<Self> {
+        let mut args = [Arg::Position(0), Arg::Position(0), Arg::Position(0)];
+        for (i, parameter) in instruction.parameters.iter().enumerate() {
+            let value = parameter.value.to_i64();
+            args[i] = match parameter.mode {
+                Mode::Immediate if instruction.operation.is_write_parameter(i) => return None,
+                Mode::Immediate => Arg::Immediate(parameter.value.clone()),
+                Mode::Position => Arg::Position(value.filter(|value| *value >= 0)? as usize),
+                Mode::Relative => Arg::Relative(value?),
+            };
+        }
+        Some(Op {
+            operation: instruction.operation,
+            args,
+            size: instruction.size(),
+        })
+    }
+}
+
+/// Compiled is a program with the op starting at each of its addresses, when there is a valid
+/// one. It is meant to be shared, behind an Arc, by the machines running the program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Compiled<W = i64> {
+    /// The memory the machines start with, sharing the program until they write to it.
+    memory: Memory<W>,
+    ops: Vec<Option<Op<W>>>,
+    /// Whether each cell of the program is part of an op, so invalidate can skip the writes to
+    /// the other cells without looking for the ops holding them.
+    code: Vec<bool>,
+}
+
+impl<W: Word> Compiled<W> {
+    pub fn new(program: &[W]) -> Self {
+        let memory = Memory::from(program);
+        let ops = (0..program.len())
+            .map(|address| {
+                Instruction::decode(&memory, address)
+                    .ok()
+                    .filter(|instruction| address + instruction.size() <= program.len())
+                    .and_then(|instruction| Op::compile(&instruction))
+            })
+            .collect::<Vec<Option<Op<W>>>>();
+        let mut code = vec![false; program.len()];
+        for (address, op) in ops.iter().enumerate() {
+            if let Some(op) = op {
+                code[address..address + op.size]
+                    .iter_mut()
+                    .for_each(|cell| *cell = true);
+            }
+        }
+        Compiled { memory, ops, code }
+    }
+
+    pub fn program(&self) -> &[W] {
+        self.memory.as_slice()
+    }
+
+    pub fn memory(&self) -> &Memory<W> {
+        &self.memory
+    }
+
+    /// is_compiled tells if the instruction at `address` was compiled, the other ones are
+    /// decoded by the machine when it reaches them.
+    pub fn is_compiled(&self, address: usize) -> bool {
+        self.op(address).is_some()
+    }
+
+    fn op(&self, address: usize) -> Option<&Op<W>> {
+        self.ops.get(address).and_then(Option::as_ref)
+    }
+}
+
+/// CodeCache is the view of a machine on a compiled program: the ops made of cells the machine
+/// wrote to are stale.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CodeCache<W> {
+    pub(crate) compiled: Arc<Compiled<W>>,
+    /// A bitset of the stale ops by starting address, empty until the first write to code.
+    stale: Vec<u64>,
+}
+
+impl<W: Word> CodeCache<W> {
+    pub(crate) fn new(compiled: Arc<Compiled<W>>) -> Self {
+        CodeCache {
+            compiled,
+            stale: vec![],
+        }
+    }
+
+    /// invalidate marks the ops holding the cell `address` as stale.
+    pub(crate) fn invalidate(&mut self, address: usize) {
+        if self.compiled.code.get(address) != Some(&true) {
+            return;
+        }
+        if self.stale.is_empty() {
+            self.stale = vec![0; self.compiled.code.len().div_ceil(64)];
+        }
+        for start in address.saturating_sub(MAX_INSTRUCTION_SIZE - 1)..=address {
+            if let Some(op) = self.compiled.op(start) {
+                if address < start + op.size {
+                    self.stale[start / 64] |= 1 << (start % 64);
+                }
+            }
+        }
+    }
+
+    fn is_stale(&self, address: usize) -> bool {
+        self.stale
+            .get(address / 64)
+            .is_some_and(|bits| bits & (1 << (address % 64)) != 0)
+    }
+
+    /// fetch returns the op at `address` if it can be used.
+    pub(crate) fn fetch(&self, address: usize) -> Option<&Op<W>> {
+        if self.is_stale(address) {
+            return None;
+        }
+        self.compiled.op(address)
+    }
+}
+
+impl<W: Word> Machine<W> {
+    /// run_op executes `op`, the instruction at the instruction pointer, like apply does, and
+    /// returns the address it wrote to.
+    pub(super) fn run_op(
+        &mut self,
+        op: &Op<W>,
+        input: &mut dyn Input<W>,
+        output: &mut dyn Output<W>,
+    ) -> Result<Option<usize>, IntcodeError<W>> {
+        let address = self.ip;
+        let mut next = address + op.size;
+        let mut write: Option<(usize, W)> = None;
+
+        match op.operation {
+            Operation::Add => {
+                let value = self
+                    .load(&op.args[0])?
+                    .checked_add(&self.load(&op.args[1])?)
+                    .ok_or(IntcodeError::Overflow { address })?;
+                write = Some((self.target(&op.args[2])?, value));
+            }
+            Operation::Mul => {
+                let value = self
+                    .load(&op.args[0])?
+                    .checked_mul(&self.load(&op.args[1])?)
+                    .ok_or(IntcodeError::Overflow { address })?;
+                write = Some((self.target(&op.args[2])?, value));
+            }
+            Operation::Ins => {
+                let target = self.target(&op.args[0])?;
+                let value = input.read().ok_or(IntcodeError::MissingInput { address })?;
+                write = Some((target, value));
+            }
+            Operation::Out => {
+                output.write(self.load(&op.args[0])?);
+            }
+            Operation::JumpIfTrue => {
+                if !self.load(&op.args[0])?.is_zero() {
+                    next = self.to_address(self.offset(&self.load(&op.args[1])?)?)?;
+                }
+            }
+            Operation::JumpIfFalse => {
+                if self.load(&op.args[0])?.is_zero() {
+                    next = self.to_address(self.offset(&self.load(&op.args[1])?)?)?;
+                }
+            }
+            Operation::LessThan => {
+                let value = W::from_bool(self.load(&op.args[0])? < self.load(&op.args[1])?);
+                write = Some((self.target(&op.args[2])?, value));
+            }
+            Operation::Equals => {
+                let value = W::from_bool(self.load(&op.args[0])? == self.load(&op.args[1])?);
+                write = Some((self.target(&op.args[2])?, value));
+            }
+            Operation::AdjustRelativeBase => {
+                let offset = self.offset(&self.load(&op.args[0])?)?;
+                self.relative_base = self
+                    .relative_base
+                    .checked_add(offset)
+                    .ok_or(IntcodeError::Overflow { address })?;
+            }
+            Operation::Halt => {
+                self.halted = true;
+            }
+        }
+        let written = write.map(|(target, value)| {
+            self.memory.write(target, value);
+            target
+        });
+        self.ip = next;
+        Ok(written)
+    }
+
+    /// target returns the address an argument points to. Immediate arguments are never written
+    /// to, such instructions are not compiled.
+    fn target(&self, arg: &Arg<W>) -> Result<usize, IntcodeError<W>> {
+        match arg {
+            Arg::Position(target) => Ok(*target),
+            Arg::Relative(offset) => self.to_address(
+                self.relative_base
+                    .checked_add(*offset)
+                    .ok_or(IntcodeError::Overflow { address: self.ip })?,
+            ),
+            Arg::Immediate(_) => unreachable!("immediate write arguments are not compiled"),
+        }
+    }
+
+    fn load(&self, arg: &Arg<W>) -> Result<W, IntcodeError<W>> {
+        match arg {
+            Arg::Immediate(value) => Ok(value.clone()),
+            _ => Ok(self.memory.read(self.target(arg)?)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::parse_program;
+    use std::collections::VecDeque;
+
+    #[test]
+    fn test_compiled_instructions() {
+        let compiled = Compiled::new(&[1002, 4, 3, 4, 1, 11101, 1, 1, 0]);
+        assert!(compiled.is_compiled(0));
+        assert!(compiled.is_compiled(1));
+        // The ADD at 5 writes to the immediate parameter 0: the interpreter reports the error.
+        assert!(!compiled.is_compiled(5));
+        // The cell 6 holds an ADD whose parameters would be past the program.
+        assert!(!compiled.is_compiled(6));
+    }
+
+    #[test]
+    fn test_self_modifying_code() {
+        // The multiplication turns the cell 4 into a HLT, which must not run as the 33 compiled.
+        let compiled = Arc::new(Compiled::new(&[1002, 4, 3, 4, 33]));
+        let mut machine = Machine::from_compiled(Arc::clone(&compiled));
+        machine.run(&mut VecDeque::new(), &mut vec![]).unwrap();
+        assert!(machine.is_halted());
+        assert_eq!(machine.memory().as_slice(), &[1002, 4, 3, 4, 99]);
+    }
+
+    #[test]
+    fn test_machines_share_the_program() {
+        let compiled = Arc::new(Compiled::new(&[1002, 4, 3, 4, 33]));
+        let mut machine = Machine::from_compiled(Arc::clone(&compiled));
+        assert!(std::ptr::eq(
+            machine.memory().as_slice(),
+            compiled.program()
+        ));
+        machine.write(0, 2);
+        assert_eq!(compiled.program(), &[1002, 4, 3, 4, 33]);
+    }
+
+    #[test]
+    fn test_step_budget() {
+        // JT #1, #0
+        let mut machine = Machine::from_compiled(Arc::new(Compiled::new(&[1105, 1, 0])));
+        machine.limit_steps(10);
+        assert_eq!(
+            machine.run(&mut VecDeque::new(), &mut vec![]),
+            Err(IntcodeError::StepBudgetExceeded {
+                address: 0,
+                budget: 10
+            })
+        );
+        assert_eq!(machine.steps(), 10);
+    }
+
+    #[test]
+    fn test_same_results_as_interpreter() {
+        let program: Vec<i64> = parse_program(include_str!("../../input/2019/day5.txt")).unwrap();
+        let compiled = Arc::new(Compiled::new(&program));
+        for input in &[1, 5] {
+            let mut interpreted: Vec<i64> = vec![];
+            let mut machine = Machine::new(&program);
+            machine
+                .run(&mut VecDeque::from(vec![*input]), &mut interpreted)
+                .unwrap();
+            let mut outputs: Vec<i64> = vec![];
+            let mut compiled_machine = Machine::from_compiled(Arc::clone(&compiled));
+            compiled_machine
+                .run(&mut VecDeque::from(vec![*input]), &mut outputs)
+                .unwrap();
+            assert_eq!(outputs, interpreted);
+            assert_eq!(compiled_machine.memory(), machine.memory());
+        }
+    }
+
+    #[test]
+    fn test_same_errors_as_interpreter() {
+        // A relative read before the start of the memory, and an input read with none left.
+        for program in &[vec![109, -5, 204, 1, 99], vec![3, 0, 3, 0, 99]] {
+            let mut interpreted = Machine::new(program);
+            let mut compiled = Machine::from_compiled(Arc::new(Compiled::new(program)));
+            assert_eq!(
+                compiled.run(&mut VecDeque::from(vec![1]), &mut vec![]),
+                interpreted.run(&mut VecDeque::from(vec![1]), &mut vec![])
+            );
+        }
+    }
+}
@@ -0,0 +1,537 @@
+// Conformance suite of the Intcode machine: every operation in every parameter mode, jumps,
+// comparisons, errors and the example programs of https://adventofcode.com/2019/day/2,
+// https://adventofcode.com/2019/day/5 and https://adventofcode.com/2019/day/9. The cases run on
+// any backend of the differential harness.
+use super::differential::Backend;
+use super::{IntcodeError, Mode, Operation};
+use std::fmt;
+
+/// MAX_STEPS bounds the runs of the cases, which all halt long before.
+const MAX_STEPS: usize = 10_000;
+
+/// The generated cases start by setting the relative base, run their instruction at CODE_START and
+/// keep their operands in the cells from DATA_START.
+const RELATIVE_BASE: i64 = 10;
+const CODE_START: usize = 2;
+const DATA_START: usize = 20;
+const X: usize = DATA_START;
+const Y: usize = DATA_START + 1;
+const TARGET: usize = DATA_START + 2;
+const PROGRAM_SIZE: usize = DATA_START + 4;
+
+const READ_MODES: [Mode; 3] = [Mode::Position, Mode::Immediate, Mode::Relative];
+const WRITE_MODES: [Mode; 2] = [Mode::Position, Mode::Relative];
+
+/// Case is a program with its inputs and what running it must produce.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Case {
+    pub name: String,
+    pub program: Vec<i64>,
+    pub inputs: Vec<i64>,
+    pub outputs: Vec<i64>,
+    /// Cells of the final memory to check, by address.
+    pub memory: Vec<(usize, i64)>,
+    /// The error the program must stop with, its outputs are checked all the same.
+    pub error: Option<IntcodeError>,
+}
+
+impl Case {
+    fn new(name: String, program: Vec<i64>) -> Self {
+        Case {
+            name,
+            program,
+            inputs: vec![],
+            outputs: vec![],
+            memory: vec![],
+            error: None,
+        }
+    }
+
+    fn inputs(mut self, inputs: &[i64]) -> Self {
+        self.inputs = Vec::from(inputs);
+        self
+    }
+
+    fn outputs(mut self, outputs: &[i64]) -> Self {
+        self.outputs = Vec::from(outputs);
+        self
+    }
+
+    fn memory(mut self, memory: &[(usize, i64)]) -> Self {
+        self.memory = Vec::from(memory);
+        self
+    }
+
+    fn error(mut self, error: IntcodeError) -> Self {
+        self.error = Some(error);
+        self
+    }
+
+    /// check runs the case on `backend` and tells the first thing it got wrong.
+    pub fn check(&self, backend: &dyn Backend) -> Result<(), String> {
+        let mut execution = backend.load(&self.program, &self.inputs);
+        let mut error = None;
+        let mut steps = 0;
+        while !execution.is_over() {
+            if steps == MAX_STEPS {
+                return Err(format!("still running after {} steps", MAX_STEPS));
+            }
+            error = execution.step().error;
+            steps += 1;
+        }
+        let expected = self.error.as_ref().map(IntcodeError::to_string);
+        if error != expected {
+            return Err(format!("expected error {:?}, found {:?}", expected, error));
+        }
+        let outputs: Vec<String> = self.outputs.iter().map(i64::to_string).collect();
+        if execution.outputs() != outputs {
+            return Err(format!(
+                "expected outputs {:?}, found {:?}",
+                outputs,
+                execution.outputs()
+            ));
+        }
+        let memory = execution.memory();
+        for (address, value) in &self.memory {
+            let found = memory.get(*address).map_or("0", String::as_str);
+            if found != value.to_string() {
+                return Err(format!(
+                    "expected [{}] = {}, found {}",
+                    address, value, found
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Failure is a case a backend got wrong.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Failure {
+    pub case: String,
+    pub backend: &'static str,
+    pub reason: String,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} fails {}: {}", self.backend, self.case, self.reason)
+    }
+}
+
+/// run_suite checks every case on every backend and returns the failures.
+pub fn run_suite(backends: &[Box<dyn Backend>]) -> Vec<Failure> {
+    let cases = cases();
+    let mut failures: Vec<Failure> = vec![];
+    for backend in backends {
+        for case in &cases {
+            if let Err(reason) = case.check(backend.as_ref()) {
+                failures.push(Failure {
+                    case: case.name.clone(),
+                    backend: backend.name(),
+                    reason,
+                });
+            }
+        }
+    }
+    failures
+}
+
+/// parameter returns the parameter reading the cell `address`, holding `value`, in `mode`.
+fn parameter(mode: Mode, address: usize, value: i64) -> i64 {
+    match mode {
+        Mode::Position => address as i64,
+        Mode::Immediate => value,
+        Mode::Relative => address as i64 - RELATIVE_BASE,
+    }
+}
+
+fn opcode(operation: Operation, modes: &[Mode]) -> i64 {
+    modes
+        .iter()
+        .rev()
+        .fold(0, |digits, mode| digits * 10 + mode.digit())
+        * 100
+        + operation.opcode()
+}
+
+/// layout returns a program setting the relative base, then running `code` from CODE_START,
+/// with `data` in its memory.
+fn layout(code: &[i64], data: &[(usize, i64)]) -> Vec<i64> {
+    let mut program = vec![
+        opcode(Operation::AdjustRelativeBase, &[Mode::Immediate]),
+        RELATIVE_BASE,
+    ];
+    program.extend_from_slice(code);
+    program.resize(PROGRAM_SIZE, 0);
+    for (address, value) in data {
+        program[*address] = *value;
+    }
+    program
+}
+
+fn binary_operation(operation: Operation, x: i64, y: i64) -> i64 {
+    match operation {
+        Operation::Add => x + y,
+        Operation::Mul => x * y,
+        Operation::LessThan => (x < y) as i64,
+        _ => (x == y) as i64,
+    }
+}
+
+fn binary_operation_cases() -> Vec<Case> {
+    let mut cases: Vec<Case> = vec![];
+    for operation in &[
+        Operation::Add,
+        Operation::Mul,
+        Operation::LessThan,
+        Operation::Equals,
+    ] {
+        for (x, y) in &[(7, 5), (5, 7), (7, 7), (-3, 4)] {
+            for first in &READ_MODES {
+                for second in &READ_MODES {
+                    for write in &WRITE_MODES {
+                        let code = [
+                            opcode(*operation, &[*first, *second, *write]),
+                            parameter(*first, X, *x),
+                            parameter(*second, Y, *y),
+                            parameter(*write, TARGET, 0),
+                            99,
+                        ];
+                        let name = format!(
+                            "{} {:?} {:?} {:?} with {} and {}",
+                            operation.mnemonic(),
+                            first,
+                            second,
+                            write,
+                            x,
+                            y
+                        );
+                        cases.push(
+                            Case::new(name, layout(&code, &[(X, *x), (Y, *y)]))
+                                .memory(&[(TARGET, binary_operation(*operation, *x, *y))]),
+                        );
+                    }
+                }
+            }
+        }
+    }
+    cases
+}
+
+fn input_output_cases() -> Vec<Case> {
+    let mut cases: Vec<Case> = vec![];
+    for write in &WRITE_MODES {
+        let code = [
+            opcode(Operation::Ins, &[*write]),
+            parameter(*write, TARGET, 0),
+            99,
+        ];
+        cases.push(
+            Case::new(format!("IN {:?}", write), layout(&code, &[]))
+                .inputs(&[42])
+                .memory(&[(TARGET, 42)]),
+        );
+    }
+    for read in &READ_MODES {
+        let code = [opcode(Operation::Out, &[*read]), parameter(*read, X, 7), 99];
+        cases.push(Case::new(format!("OUT {:?}", read), layout(&code, &[(X, 7)])).outputs(&[7]));
+    }
+    cases
+}
+
+/// jump_cases jump over an output of 1 to an output of 2 when the jump is taken.
+fn jump_cases() -> Vec<Case> {
+    let jump_target = CODE_START as i64 + 6;
+    let mut cases: Vec<Case> = vec![];
+    for operation in &[Operation::JumpIfTrue, Operation::JumpIfFalse] {
+        for condition in &[0, 7] {
+            for first in &READ_MODES {
+                for second in &READ_MODES {
+                    let code = [
+                        opcode(*operation, &[*first, *second]),
+                        parameter(*first, X, *condition),
+                        parameter(*second, Y, jump_target),
+                        104,
+                        1,
+                        99,
+                        104,
+                        2,
+                        99,
+                    ];
+                    let taken = (*condition != 0) == (*operation == Operation::JumpIfTrue);
+                    let name = format!(
+                        "{} {:?} {:?} with {}",
+                        operation.mnemonic(),
+                        first,
+                        second,
+                        condition
+                    );
+                    let data = [(X, *condition), (Y, jump_target)];
+                    cases.push(Case::new(name, layout(&code, &data)).outputs(&[if taken {
+                        2
+                    } else {
+                        1
+                    }]));
+                }
+            }
+        }
+    }
+    cases
+}
+
+/// relative_base_cases adjust the relative base by 5 and output the cell it then points to.
+fn relative_base_cases() -> Vec<Case> {
+    let pointed = (RELATIVE_BASE + 5) as usize;
+    READ_MODES
+        .iter()
+        .map(|read| {
+            let code = [
+                opcode(Operation::AdjustRelativeBase, &[*read]),
+                parameter(*read, X, 5),
+                204,
+                0,
+                99,
+            ];
+            Case::new(
+                format!("ARB {:?}", read),
+                layout(&code, &[(X, 5), (pointed, 33)]),
+            )
+            .outputs(&[33])
+        })
+        .collect()
+}
+
+fn error_cases() -> Vec<Case> {
+    let address = CODE_START;
+    let mut cases = vec![
+        Case::new(String::from("unknown opcode"), layout(&[42], &[])).error(
+            IntcodeError::UnknownOpcode {
+                address,
+                opcode: 42,
+            },
+        ),
+        Case::new(
+            String::from("invalid parameter mode"),
+            layout(&[304, 0], &[]),
+        )
+        .error(IntcodeError::InvalidParameterMode {
+            address,
+            parameter: 0,
+            mode: 3,
+        }),
+        Case::new(String::from("negative position"), layout(&[4, -1], &[])).error(
+            IntcodeError::NegativeAddress {
+                address,
+                target: -1,
+            },
+        ),
+        Case::new(
+            String::from("negative relative address"),
+            layout(&[204, -11], &[]),
+        )
+        .error(IntcodeError::NegativeAddress {
+            address,
+            target: -1,
+        }),
+        Case::new(String::from("negative jump"), layout(&[1105, 1, -2], &[])).error(
+            IntcodeError::NegativeAddress {
+                address,
+                target: -2,
+            },
+        ),
+        Case::new(String::from("missing input"), layout(&[104, 1, 3, 0], &[]))
+            .outputs(&[1])
+            .error(IntcodeError::MissingInput {
+                address: address + 2,
+            }),
+        Case::new(
+            String::from("overflow"),
+            layout(&[1102, i64::MAX, 2, TARGET as i64], &[]),
+        )
+        .error(IntcodeError::Overflow { address }),
+    ];
+    for operation in &[
+        Operation::Add,
+        Operation::Mul,
+        Operation::LessThan,
+        Operation::Equals,
+    ] {
+        let code = [
+            opcode(*operation, &[Mode::Immediate; 3]),
+            1,
+            1,
+            TARGET as i64,
+        ];
+        cases.push(
+            Case::new(
+                format!("{} writing in immediate mode", operation.mnemonic()),
+                layout(&code, &[]),
+            )
+            .error(IntcodeError::WriteInImmediateMode {
+                address,
+                parameter: 2,
+            }),
+        );
+    }
+    cases.push(
+        Case::new(
+            String::from("IN writing in immediate mode"),
+            layout(&[103, 0], &[]),
+        )
+        .inputs(&[1])
+        .error(IntcodeError::WriteInImmediateMode {
+            address,
+            parameter: 0,
+        }),
+    );
+    cases
+}
+
+fn example_cases() -> Vec<Case> {
+    let example = |name: &str, program: &[i64]| Case::new(String::from(name), Vec::from(program));
+    let equal_to_8 = [3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8];
+    let less_than_8 = [3, 9, 7, 9, 10, 9, 4, 9, 99, -1, 8];
+    let equal_to_8_immediate = [3, 3, 1108, -1, 8, 3, 4, 3, 99];
+    let less_than_8_immediate = [3, 3, 1107, -1, 8, 3, 4, 3, 99];
+    let is_non_zero = [3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9];
+    let is_non_zero_immediate = [3, 3, 1105, -1, 9, 1101, 0, 0, 12, 4, 12, 99, 1];
+    let compare_to_8 = [
+        3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0, 0,
+        1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4, 20,
+        1105, 1, 46, 98, 99,
+    ];
+    let quine = [
+        109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+    ];
+
+    let mut cases = vec![
+        example("day2 add", &[1, 0, 0, 0, 99]).memory(&[(0, 2)]),
+        example("day2 mul", &[2, 3, 0, 3, 99]).memory(&[(3, 6)]),
+        example("day2 mul past the halt", &[2, 4, 4, 5, 99, 0]).memory(&[(5, 9801)]),
+        example("day2 self-modifying", &[1, 1, 1, 4, 99, 5, 6, 0, 99]).memory(&[(0, 30), (4, 2)]),
+        example("day2 program", &[1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50])
+            .memory(&[(0, 3500), (3, 70)]),
+        example("day5 echo", &[3, 0, 4, 0, 99])
+            .inputs(&[-8])
+            .outputs(&[-8]),
+        example("day5 parameter modes", &[1002, 4, 3, 4, 33]).memory(&[(4, 99)]),
+        example("day5 negative values", &[1101, 100, -1, 4, 0]).memory(&[(4, 99)]),
+        example("day9 quine", &quine).outputs(&quine),
+        example(
+            "day9 16 digits",
+            &[1102, 34915192, 34915192, 7, 4, 7, 99, 0],
+        )
+        .outputs(&[1219070632396864]),
+        example("day9 large number", &[104, 1125899906842624, 99]).outputs(&[1125899906842624]),
+    ];
+    for input in 7..=9 {
+        let compare = |name: &str, program: &[i64], output: bool| {
+            example(&format!("{} with {}", name, input), program)
+                .inputs(&[input])
+                .outputs(&[output as i64])
+        };
+        cases.push(compare("day5 equal to 8", &equal_to_8, input == 8));
+        cases.push(compare("day5 less than 8", &less_than_8, input < 8));
+        cases.push(compare(
+            "day5 equal to 8 immediate",
+            &equal_to_8_immediate,
+            input == 8,
+        ));
+        cases.push(compare(
+            "day5 less than 8 immediate",
+            &less_than_8_immediate,
+            input < 8,
+        ));
+        cases.push(
+            example(&format!("day5 compare to 8 with {}", input), &compare_to_8)
+                .inputs(&[input])
+                .outputs(&[992 + input]),
+        );
+    }
+    for input in 0..=1 {
+        cases.push(
+            example(&format!("day5 jumps with {}", input), &is_non_zero)
+                .inputs(&[input])
+                .outputs(&[input]),
+        );
+        cases.push(
+            example(
+                &format!("day5 immediate jumps with {}", input),
+                &is_non_zero_immediate,
+            )
+            .inputs(&[input])
+            .outputs(&[input]),
+        );
+    }
+    cases
+}
+
+/// cases returns the whole conformance suite.
+pub fn cases() -> Vec<Case> {
+    let mut cases = binary_operation_cases();
+    cases.extend(input_output_cases());
+    cases.extend(jump_cases());
+    cases.extend(relative_base_cases());
+    cases.push(Case::new(String::from("HLT"), layout(&[99], &[])));
+    cases.extend(error_cases());
+    cases.extend(example_cases());
+    cases
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::differential::{backends, differential, Interpreter};
+
+    #[test]
+    fn test_suite_covers_every_operation() {
+        let cases = cases();
+        for operation in &[
+            Operation::Add,
+            Operation::Mul,
+            Operation::Ins,
+            Operation::Out,
+            Operation::JumpIfTrue,
+            Operation::JumpIfFalse,
+            Operation::LessThan,
+            Operation::Equals,
+            Operation::AdjustRelativeBase,
+            Operation::Halt,
+        ] {
+            assert!(cases
+                .iter()
+                .any(|case| case.name.starts_with(operation.mnemonic())));
+        }
+    }
+
+    #[test]
+    fn test_every_backend_conforms() {
+        let failures: Vec<String> = run_suite(&backends())
+            .iter()
+            .map(Failure::to_string)
+            .collect();
+        assert_eq!(failures, Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_backends_agree_on_the_suite() {
+        for case in cases() {
+            assert_eq!(
+                differential(&case.program, &case.inputs, &backends(), MAX_STEPS).err(),
+                None,
+                "{}",
+                case.name
+            );
+        }
+    }
+
+    #[test]
+    fn test_failure() {
+        let case = Case::new(String::from("wrong"), vec![104, 1, 99]).outputs(&[2]);
+        assert_eq!(
+            case.check(&Interpreter),
+            Err(String::from("expected outputs [\"2\"], found [\"1\"]"))
+        );
+    }
+}
@@ -0,0 +1,495 @@
+// Step-through debugger for the Intcode machine.
+//
+// It can be driven from code, from a script of commands (see Debugger::execute) or interactively
+// from a terminal (see Debugger::run_interactive). The commands are:
+//
+//     step [n] | s [n]          execute n instructions (1 by default), or up to a breakpoint
+//     continue | c              run until a breakpoint, a watchpoint, an input or the end
+//     break | b <address|MNEMONIC>
+//                               stop before executing an address or an operation
+//     delete <address|MNEMONIC> remove a breakpoint
+//     watch | w <address>       stop when the cell at address changes
+//     unwatch <address>         remove a watchpoint
+//     input | i <value>...      queue values for the `IN` instructions
+//     registers | r             show the instruction pointer and relative base
+//     disassemble | d           show the current instruction
+//     memory <address> [n] | x  show n cells starting at address (8 by default)
+use super::disassembler::Line;
+use super::{Instruction, IntcodeError, Machine, Operation, Word};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+const DEFAULT_MEMORY_DUMP_SIZE: usize = 8;
+const PROMPT: &str = "(intcode) ";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Breakpoint {
+    Address(usize),
+    Operation(Operation),
+}
+
+/// Stop is the reason the debugger gave control back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Stop<W = i64> {
+    /// The requested number of instructions was executed.
+    Stepped,
+    Breakpoint(Breakpoint),
+    Watchpoint {
+        address: usize,
+        old: W,
+        new: W,
+    },
+    NeedsInput,
+    Halted,
+    Error(IntcodeError<W>),
+}
+
+impl<W: Word> fmt::Display for Stop<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Stop::Stepped => write!(f, "stepped"),
+            Stop::Breakpoint(Breakpoint::Address(address)) => {
+                write!(f, "breakpoint at address {}", address)
+            }
+            Stop::Breakpoint(Breakpoint::Operation(operation)) => {
+                write!(f, "breakpoint on {}", operation.mnemonic())
+            }
+            Stop::Watchpoint { address, old, new } => {
+                write!(f, "watchpoint on {}: {} -> {}", address, old, new)
+            }
+            Stop::NeedsInput => write!(f, "waiting for input"),
+            Stop::Halted => write!(f, "halted"),
+            Stop::Error(error) => write!(f, "error: {}", error),
+        }
+    }
+}
+
+pub struct Debugger<W = i64> {
+    machine: Machine<W>,
+    breakpoints: BTreeSet<Breakpoint>,
+    watchpoints: BTreeSet<usize>,
+    inputs: VecDeque<W>,
+    outputs: Vec<W>,
+}
+
+impl<W: Word> Debugger<W> {
+    pub fn new(program: &[W]) -> Self {
+        Debugger {
+            machine: Machine::new(program),
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeSet::new(),
+            inputs: VecDeque::new(),
+            outputs: vec![],
+        }
+    }
+
+    pub fn machine(&self) -> &Machine<W> {
+        &self.machine
+    }
+
+    /// outputs returns every value output by the program so far.
+    pub fn outputs(&self) -> &[W] {
+        &self.outputs
+    }
+
+    pub fn push_input(&mut self, value: W) {
+        self.inputs.push_back(value);
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        self.breakpoints.insert(breakpoint);
+    }
+
+    pub fn remove_breakpoint(&mut self, breakpoint: Breakpoint) -> bool {
+        self.breakpoints.remove(&breakpoint)
+    }
+
+    pub fn watch(&mut self, address: usize) {
+        self.watchpoints.insert(address);
+    }
+
+    pub fn unwatch(&mut self, address: usize) -> bool {
+        self.watchpoints.remove(&address)
+    }
+
+    /// breakpoint returns the breakpoint matching the instruction under the instruction pointer.
+    fn breakpoint(&self) -> Option<Breakpoint> {
+        let ip = self.machine.ip();
+        if self.breakpoints.contains(&Breakpoint::Address(ip)) {
+            return Some(Breakpoint::Address(ip));
+        }
+        let instruction = Instruction::decode(self.machine.memory(), ip).ok()?;
+        Some(Breakpoint::Operation(instruction.operation)).filter(|b| self.breakpoints.contains(b))
+    }
+
+    /// execute_one runs the instruction under the instruction pointer, returning why the
+    /// debugger has to stop after it, if it has to.
+    fn execute_one(&mut self) -> Option<Stop<W>> {
+        if self.machine.is_halted() || self.machine.ip() >= self.machine.memory().len() {
+            return Some(Stop::Halted);
+        }
+        let watched: BTreeMap<usize, W> = self
+            .watchpoints
+            .iter()
+            .map(|address| (*address, self.machine.read(*address)))
+            .collect();
+        match self.machine.step(&mut self.inputs, &mut self.outputs) {
+            Err(IntcodeError::MissingInput { .. }) => return Some(Stop::NeedsInput),
+            Err(error) => return Some(Stop::Error(error)),
+            Ok(()) => (),
+        }
+        for (address, old) in watched {
+            let new = self.machine.read(address);
+            if new != old {
+                return Some(Stop::Watchpoint { address, old, new });
+            }
+        }
+        if self.machine.is_halted() {
+            return Some(Stop::Halted);
+        }
+        None
+    }
+
+    /// step executes `count` instructions, stopping early on breakpoints, watchpoints, inputs or
+    /// errors. Like for resume, a breakpoint on the current instruction is ignored.
+    pub fn step(&mut self, count: usize) -> Stop<W> {
+        for i in 0..count {
+            if let Some(stop) = self.execute_one() {
+                return stop;
+            }
+            if i + 1 < count {
+                if let Some(breakpoint) = self.breakpoint() {
+                    return Stop::Breakpoint(breakpoint);
+                }
+            }
+        }
+        Stop::Stepped
+    }
+
+    /// resume runs the program until it stops. A breakpoint on the current instruction is
+    /// ignored, so resuming from a breakpoint moves on.
+    pub fn resume(&mut self) -> Stop<W> {
+        loop {
+            if let Some(stop) = self.execute_one() {
+                return stop;
+            }
+            if let Some(breakpoint) = self.breakpoint() {
+                return Stop::Breakpoint(breakpoint);
+            }
+        }
+    }
+
+    pub fn registers(&self) -> String {
+        format!(
+            "ip={} rb={} halted={}",
+            self.machine.ip(),
+            self.machine.relative_base(),
+            self.machine.is_halted()
+        )
+    }
+
+    /// current_instruction returns the disassembly of the instruction under the instruction
+    /// pointer.
+    pub fn current_instruction(&self) -> String {
+        let address = self.machine.ip();
+        let line = match Instruction::decode(self.machine.memory(), address) {
+            Ok(instruction) => Line::Instruction {
+                words: (address..address + instruction.size())
+                    .map(|a| self.machine.read(a))
+                    .collect(),
+                address,
+                instruction,
+            },
+            Err(_) => Line::Data {
+                address,
+                value: self.machine.read(address),
+            },
+        };
+        line.to_string().trim_end().to_string()
+    }
+
+    fn memory_dump(&self, start: usize, count: usize) -> String {
+        let end = match start.checked_add(count) {
+            Some(end) => end,
+            None => return format!("invalid memory range {} {}", start, count),
+        };
+        let cells: Vec<String> = (start..end)
+            .map(|address| self.machine.read(address).to_string())
+            .collect();
+        format!("{:>5}: {}", start, cells.join(" "))
+    }
+
+    /// execute runs a debugger command and returns what it prints.
+    pub fn execute(&mut self, command: &str) -> String {
+        let mut words = command.split_whitespace();
+        let name = match words.next() {
+            Some(name) => name,
+            None => return String::new(),
+        };
+        let arguments: Vec<&str> = words.collect();
+        let outputs_before = self.outputs.len();
+        let mut result = match (name, arguments.as_slice()) {
+            ("step", []) | ("s", []) => {
+                let stop = self.step(1);
+                self.report(stop)
+            }
+            ("step", [count]) | ("s", [count]) => match count.parse() {
+                Ok(count) => {
+                    let stop = self.step(count);
+                    self.report(stop)
+                }
+                Err(_) => format!("invalid count {}", count),
+            },
+            ("continue", []) | ("c", []) => {
+                let stop = self.resume();
+                self.report(stop)
+            }
+            ("break", [target]) | ("b", [target]) => match parse_breakpoint(target) {
+                Some(breakpoint) => {
+                    self.add_breakpoint(breakpoint);
+                    format!("breakpoint set on {}", target)
+                }
+                None => format!("invalid breakpoint {}", target),
+            },
+            ("delete", [target]) => match parse_breakpoint(target) {
+                Some(breakpoint) if self.remove_breakpoint(breakpoint) => {
+                    format!("breakpoint on {} deleted", target)
+                }
+                _ => format!("no breakpoint on {}", target),
+            },
+            ("watch", [address]) | ("w", [address]) => match address.parse() {
+                Ok(address) => {
+                    self.watch(address);
+                    format!("watching {}", address)
+                }
+                Err(_) => format!("invalid address {}", address),
+            },
+            ("unwatch", [address]) => match address.parse() {
+                Ok(address) if self.unwatch(address) => format!("stopped watching {}", address),
+                _ => format!("no watchpoint on {}", address),
+            },
+            ("input", values) | ("i", values) if !values.is_empty() => {
+                match values
+                    .iter()
+                    .map(|v| v.parse())
+                    .collect::<Result<Vec<W>, _>>()
+                {
+                    Ok(values) => {
+                        let count = values.len();
+                        self.inputs.extend(values);
+                        format!("{} value(s) queued", count)
+                    }
+                    Err(_) => format!("invalid input {}", values.join(" ")),
+                }
+            }
+            ("registers", []) | ("r", []) => self.registers(),
+            ("disassemble", []) | ("d", []) => self.current_instruction(),
+            ("memory", [address]) | ("x", [address]) => match address.parse() {
+                Ok(address) => self.memory_dump(address, DEFAULT_MEMORY_DUMP_SIZE),
+                Err(_) => format!("invalid address {}", address),
+            },
+            ("memory", [address, count]) | ("x", [address, count]) => {
+                match (address.parse(), count.parse()) {
+                    (Ok(address), Ok(count)) => self.memory_dump(address, count),
+                    _ => format!("invalid memory range {} {}", address, count),
+                }
+            }
+            _ => format!("unknown command {}", command.trim()),
+        };
+        let outputs: Vec<String> = self.outputs[outputs_before..]
+            .iter()
+            .map(|value| format!("output: {}", value))
+            .collect();
+        if !outputs.is_empty() {
+            result = format!("{}\n{}", outputs.join("\n"), result);
+        }
+        result
+    }
+
+    fn report(&self, stop: Stop<W>) -> String {
+        format!("{}\n{}", stop, self.current_instruction())
+    }
+
+    /// run_script executes one command per line and returns the transcript of the session.
+    pub fn run_script(&mut self, script: &str) -> String {
+        script
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| format!("{}{}\n{}\n", PROMPT, line.trim(), self.execute(line)))
+            .collect()
+    }
+
+    /// run_interactive reads commands from `input` until `quit` or the end of the input.
+    pub fn run_interactive<R: BufRead, O: Write>(
+        &mut self,
+        input: R,
+        mut output: O,
+    ) -> io::Result<()> {
+        write!(output, "{}\n{}", self.current_instruction(), PROMPT)?;
+        output.flush()?;
+        for line in input.lines() {
+            let line = line?;
+            if matches!(line.trim(), "quit" | "q") {
+                break;
+            }
+            write!(output, "{}\n{}", self.execute(&line), PROMPT)?;
+            output.flush()?;
+        }
+        writeln!(output)
+    }
+}
+
+fn parse_breakpoint(target: &str) -> Option<Breakpoint> {
+    match target.parse() {
+        Ok(address) => Some(Breakpoint::Address(address)),
+        Err(_) => Operation::from_mnemonic(target).map(Breakpoint::Operation),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::assembler::assemble;
+
+    /// outputs its input down to 1, keeping the counter at address 12
+    fn countdown() -> Vec<i64> {
+        assemble(
+            "
+                   IN  [value]
+            loop:  OUT [value]
+                   ADD [value], #-1, [value]
+                   JT  [value], #loop
+                   HLT
+            value: DATA 0
+            ",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_step() {
+        let mut debugger = Debugger::new(&countdown());
+        assert_eq!(debugger.step(1), Stop::NeedsInput);
+        debugger.push_input(2);
+        assert_eq!(debugger.step(2), Stop::Stepped);
+        assert_eq!(debugger.machine().ip(), 4);
+        assert_eq!(debugger.outputs(), &[2]);
+        assert_eq!(debugger.step(100), Stop::Halted);
+        assert_eq!(debugger.outputs(), &[2, 1]);
+
+        let mut debugger = Debugger::new(&countdown());
+        debugger.push_input(3);
+        debugger.add_breakpoint(Breakpoint::Address(8));
+        assert_eq!(debugger.step(100), Stop::Breakpoint(Breakpoint::Address(8)));
+        assert_eq!(debugger.step(1), Stop::Stepped);
+        assert_eq!(debugger.step(100), Stop::Breakpoint(Breakpoint::Address(8)));
+        assert_eq!(debugger.outputs(), &[3, 2]);
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let mut debugger = Debugger::new(&countdown());
+        debugger.push_input(3);
+        debugger.add_breakpoint(Breakpoint::Address(8));
+        assert_eq!(debugger.resume(), Stop::Breakpoint(Breakpoint::Address(8)));
+        assert_eq!(debugger.resume(), Stop::Breakpoint(Breakpoint::Address(8)));
+        assert_eq!(debugger.outputs(), &[3, 2]);
+        debugger.remove_breakpoint(Breakpoint::Address(8));
+        debugger.add_breakpoint(Breakpoint::Operation(Operation::Halt));
+        assert_eq!(
+            debugger.resume(),
+            Stop::Breakpoint(Breakpoint::Operation(Operation::Halt))
+        );
+        assert_eq!(debugger.resume(), Stop::Halted);
+    }
+
+    #[test]
+    fn test_watchpoints() {
+        let mut debugger = Debugger::new(&countdown());
+        debugger.push_input(2);
+        debugger.watch(12);
+        assert_eq!(
+            debugger.resume(),
+            Stop::Watchpoint {
+                address: 12,
+                old: 0,
+                new: 2
+            }
+        );
+        assert_eq!(
+            debugger.resume(),
+            Stop::Watchpoint {
+                address: 12,
+                old: 2,
+                new: 1
+            }
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut debugger = Debugger::new(&[1101, 0, 0, 0, 42]);
+        assert_eq!(
+            debugger.resume(),
+            Stop::Error(IntcodeError::UnknownOpcode {
+                address: 4,
+                opcode: 42
+            })
+        );
+        assert_eq!(debugger.current_instruction(), "    4: DATA 42");
+        assert_eq!(
+            debugger.execute("x 18446744073709551615 2"),
+            "invalid memory range 18446744073709551615 2"
+        );
+    }
+
+    #[test]
+    fn test_script() {
+        let mut debugger = Debugger::new(&countdown());
+        let transcript = debugger.run_script(
+            "
+            break JT
+            input 1
+            c
+            r
+            x 12 2
+            watch 12
+            s
+            ",
+        );
+        assert_eq!(
+            transcript,
+            "(intcode) break JT\n\
+             breakpoint set on JT\n\
+             (intcode) input 1\n\
+             1 value(s) queued\n\
+             (intcode) c\n\
+             output: 1\n\
+             breakpoint on JT\n\
+             \x20   8: JT  [12], #2                     ; 1005,12,2\n\
+             (intcode) r\n\
+             ip=8 rb=0 halted=false\n\
+             (intcode) x 12 2\n\
+             \x20  12: 0 0\n\
+             (intcode) watch 12\n\
+             watching 12\n\
+             (intcode) s\n\
+             stepped\n\
+             \x20  11: HLT                              ; 99\n"
+        );
+    }
+
+    #[test]
+    fn test_interactive() {
+        let mut debugger = Debugger::new(&countdown());
+        let mut output: Vec<u8> = vec![];
+        debugger
+            .run_interactive(&b"input 1\nd\nquit\nstep\n"[..], &mut output)
+            .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("    0: IN  [12]"));
+        assert!(output.contains("1 value(s) queued\n(intcode) "));
+        assert_eq!(debugger.machine().ip(), 0);
+    }
+}
@@ -0,0 +1,405 @@
+// Differential testing of the ways this crate can run Intcode programs: every backend runs the
+// same program one instruction at a time and the harness reports the first step they disagree on.
+use super::compiled::Compiled;
+use super::{Machine, Word};
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::Arc;
+
+/// Observation is what the harness compares between backends after each step. Words are compared
+/// as text, so backends with different word types can be compared.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Observation {
+    pub ip: usize,
+    pub relative_base: i64,
+    pub halted: bool,
+    /// The value output by the step.
+    pub output: Option<String>,
+    /// The error the step failed with, see IntcodeError's Display.
+    pub error: Option<String>,
+}
+
+impl fmt::Display for Observation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ip={} rb={}", self.ip, self.relative_base)?;
+        if self.halted {
+            write!(f, " halted")?;
+        }
+        if let Some(output) = &self.output {
+            write!(f, " output={}", output)?;
+        }
+        if let Some(error) = &self.error {
+            write!(f, " error: {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+/// Execution is a program being run by a backend, one instruction at a time.
+pub trait Execution {
+    /// step executes the next instruction and tells what it did.
+    fn step(&mut self) -> Observation;
+
+    /// is_over tells if the program halted, left the memory or failed.
+    fn is_over(&self) -> bool;
+
+    fn outputs(&self) -> Vec<String>;
+
+    /// memory returns the dense part of the memory, see Memory::as_slice.
+    fn memory(&self) -> Vec<String>;
+}
+
+/// Backend is a way to run Intcode programs.
+pub trait Backend {
+    fn name(&self) -> &'static str;
+
+    /// load returns the execution of `program` reading `inputs`.
+    fn load(&self, program: &[i64], inputs: &[i64]) -> Box<dyn Execution>;
+}
+
+struct MachineExecution<W> {
+    machine: Machine<W>,
+    inputs: VecDeque<W>,
+    outputs: Vec<W>,
+    failed: bool,
+}
+
+impl<W: Word + From<i64>> MachineExecution<W> {
+    fn new(machine: Machine<W>, inputs: &[i64]) -> Self {
+        MachineExecution {
+            machine,
+            inputs: inputs.iter().map(|input| W::from(*input)).collect(),
+            outputs: vec![],
+            failed: false,
+        }
+    }
+}
+
+impl<W: Word + From<i64>> Execution for MachineExecution<W> {
+    fn step(&mut self) -> Observation {
+        let outputs = self.outputs.len();
+        let error = match self.machine.step(&mut self.inputs, &mut self.outputs) {
+            Ok(()) => None,
+            Err(error) => {
+                self.failed = true;
+                Some(error.to_string())
+            }
+        };
+        Observation {
+            ip: self.machine.ip(),
+            relative_base: self.machine.relative_base(),
+            halted: self.machine.is_halted(),
+            output: self.outputs[outputs..].first().map(W::to_string),
+            error,
+        }
+    }
+
+    fn is_over(&self) -> bool {
+        self.failed || self.machine.is_halted() || self.machine.ip() >= self.machine.memory().len()
+    }
+
+    fn outputs(&self) -> Vec<String> {
+        self.outputs.iter().map(W::to_string).collect()
+    }
+
+    fn memory(&self) -> Vec<String> {
+        self.machine
+            .memory()
+            .as_slice()
+            .iter()
+            .map(W::to_string)
+            .collect()
+    }
+}
+
+fn words<W: Word + From<i64>>(program: &[i64]) -> Vec<W> {
+    program.iter().map(|word| W::from(*word)).collect()
+}
+
+/// Interpreter decodes every instruction when it reaches it.
+pub struct Interpreter;
+
+impl Backend for Interpreter {
+    fn name(&self) -> &'static str {
+        "interpreter"
+    }
+
+    fn load(&self, program: &[i64], inputs: &[i64]) -> Box<dyn Execution> {
+        Box::new(MachineExecution::new(Machine::new(program), inputs))
+    }
+}
+
+/// CompiledProgram runs the program compiled ahead of time, see Compiled.
+pub struct CompiledProgram;
+
+impl Backend for CompiledProgram {
+    fn name(&self) -> &'static str {
+        "compiled"
+    }
+
+    fn load(&self, program: &[i64], inputs: &[i64]) -> Box<dyn Execution> {
+        let machine = Machine::from_compiled(Arc::new(Compiled::new(program)));
+        Box::new(MachineExecution::new(machine, inputs))
+    }
+}
+
+/// Traced runs the interpreter with tracing and code write detection enabled, which resolve the
+/// operands of every instruction on their own.
+pub struct Traced;
+
+impl Backend for Traced {
+    fn name(&self) -> &'static str {
+        "traced"
+    }
+
+    fn load(&self, program: &[i64], inputs: &[i64]) -> Box<dyn Execution> {
+        let mut machine = Machine::new(program);
+        machine.enable_trace();
+        machine.detect_code_writes();
+        Box::new(MachineExecution::new(machine, inputs))
+    }
+}
+
+/// Wide runs the interpreter with i128 words, which only diverges from the other backends when
+/// they overflow.
+pub struct Wide;
+
+impl Backend for Wide {
+    fn name(&self) -> &'static str {
+        "i128"
+    }
+
+    fn load(&self, program: &[i64], inputs: &[i64]) -> Box<dyn Execution> {
+        let machine: Machine<i128> = Machine::new(&words(program));
+        Box::new(MachineExecution::new(machine, inputs))
+    }
+}
+
+/// Big runs the interpreter with arbitrary-precision words.
+#[cfg(feature = "bigint")]
+pub struct Big;
+
+#[cfg(feature = "bigint")]
+impl Backend for Big {
+    fn name(&self) -> &'static str {
+        "bigint"
+    }
+
+    fn load(&self, program: &[i64], inputs: &[i64]) -> Box<dyn Execution> {
+        let machine: Machine<num_bigint::BigInt> = Machine::new(&words(program));
+        Box::new(MachineExecution::new(machine, inputs))
+    }
+}
+
+/// backends returns every backend of the crate with i64 words, the interpreter first.
+pub fn backends() -> Vec<Box<dyn Backend>> {
+    vec![
+        Box::new(Interpreter),
+        Box::new(CompiledProgram),
+        Box::new(Traced),
+    ]
+}
+
+/// Divergence is the first difference between the reference backend and another one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// The number of instructions both executed before, the step of the difference.
+    pub step: usize,
+    pub reference: &'static str,
+    pub backend: &'static str,
+    pub kind: DivergenceKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DivergenceKind {
+    Step {
+        expected: Observation,
+        found: Observation,
+    },
+    /// The runs ended the same way but left different memories.
+    Memory {
+        address: usize,
+        expected: Option<String>,
+        found: Option<String>,
+    },
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} diverges from {} at step {}: ",
+            self.backend, self.reference, self.step
+        )?;
+        let cell = |value: &Option<String>| value.clone().unwrap_or_else(|| String::from("none"));
+        match &self.kind {
+            DivergenceKind::Step { expected, found } => {
+                write!(f, "expected {}, found {}", expected, found)
+            }
+            DivergenceKind::Memory {
+                address,
+                expected,
+                found,
+            } => write!(
+                f,
+                "expected [{}] = {}, found {}",
+                address,
+                cell(expected),
+                cell(found)
+            ),
+        }
+    }
+}
+
+/// Agreement is the common result of the backends when they did not diverge.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Agreement {
+    pub steps: usize,
+    pub outputs: Vec<String>,
+    /// Whether the runs ended before max_steps.
+    pub finished: bool,
+}
+
+/// differential runs `program` on every backend in lockstep, for at most `max_steps` instructions,
+/// comparing each one with the first backend.
+pub fn differential(
+    program: &[i64],
+    inputs: &[i64],
+    backends: &[Box<dyn Backend>],
+    max_steps: usize,
+) -> Result<Agreement, Box<Divergence>> {
+    let (reference, others) = match backends.split_first() {
+        Some(split) => split,
+        None => {
+            return Ok(Agreement {
+                steps: 0,
+                outputs: vec![],
+                finished: true,
+            })
+        }
+    };
+    let mut expected = reference.load(program, inputs);
+    let mut executions: Vec<Box<dyn Execution>> = others
+        .iter()
+        .map(|backend| backend.load(program, inputs))
+        .collect();
+    let divergence = |backend: &dyn Backend, step: usize, kind: DivergenceKind| {
+        Box::new(Divergence {
+            step,
+            reference: reference.name(),
+            backend: backend.name(),
+            kind,
+        })
+    };
+
+    let mut steps = 0;
+    while steps < max_steps && !expected.is_over() {
+        let observation = expected.step();
+        for (backend, execution) in others.iter().zip(&mut executions) {
+            let found = execution.step();
+            if found != observation {
+                let kind = DivergenceKind::Step {
+                    expected: observation,
+                    found,
+                };
+                return Err(divergence(backend.as_ref(), steps, kind));
+            }
+        }
+        steps += 1;
+    }
+
+    let memory = expected.memory();
+    for (backend, execution) in others.iter().zip(&executions) {
+        let found = execution.memory();
+        if let Some(address) = (0..memory.len().max(found.len()))
+            .find(|address| memory.get(*address) != found.get(*address))
+        {
+            let kind = DivergenceKind::Memory {
+                address,
+                expected: memory.get(address).cloned(),
+                found: found.get(address).cloned(),
+            };
+            return Err(divergence(backend.as_ref(), steps, kind));
+        }
+    }
+    Ok(Agreement {
+        steps,
+        outputs: expected.outputs(),
+        finished: expected.is_over(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::parse_program;
+
+    /// Forgetful is a broken backend for the tests: it ignores the instructions adjusting the
+    /// relative base.
+    struct Forgetful;
+
+    impl Backend for Forgetful {
+        fn name(&self) -> &'static str {
+            "forgetful"
+        }
+
+        fn load(&self, program: &[i64], inputs: &[i64]) -> Box<dyn Execution> {
+            let program: Vec<i64> = program
+                .iter()
+                .map(|word| if word % 100 == 9 { 1106 } else { *word })
+                .collect();
+            Interpreter.load(&program, inputs)
+        }
+    }
+
+    #[test]
+    fn test_backends_agree_on_day5() {
+        let program: Vec<i64> = parse_program(include_str!("../../input/2019/day5.txt")).unwrap();
+        let mut backends = backends();
+        backends.push(Box::new(Wide));
+        #[cfg(feature = "bigint")]
+        backends.push(Box::new(Big));
+        for input in &[1, 5] {
+            let agreement = differential(&program, &[*input], &backends, 10_000).unwrap();
+            assert!(agreement.finished);
+            let (code, checks) = agreement.outputs.split_last().unwrap();
+            assert!(checks.iter().all(|check| check == "0"));
+            assert_ne!(code, "0");
+        }
+    }
+
+    #[test]
+    fn test_step_divergence() {
+        // ARB #1, OUT [rb+0], HLT
+        let program = vec![109, 1, 204, 0, 99];
+        let backends: Vec<Box<dyn Backend>> = vec![Box::new(Interpreter), Box::new(Forgetful)];
+        let divergence = differential(&program, &[], &backends, 100).unwrap_err();
+        assert_eq!(divergence.step, 0);
+        assert_eq!(
+            divergence.to_string(),
+            "forgetful diverges from interpreter at step 0: expected ip=2 rb=1, found ip=3 rb=0"
+        );
+    }
+
+    #[test]
+    fn test_overflow_divergence() {
+        let program = vec![1102, i64::MAX, 2, 7, 4, 7, 99, 0];
+        let backends: Vec<Box<dyn Backend>> = vec![Box::new(Interpreter), Box::new(Wide)];
+        let divergence = differential(&program, &[], &backends, 100).unwrap_err();
+        match (divergence.step, divergence.kind) {
+            (0, DivergenceKind::Step { expected, found }) => {
+                assert!(expected.error.is_some());
+                assert_eq!(found.error, None);
+            }
+            divergence => panic!("unexpected divergence {:?}", divergence),
+        }
+    }
+
+    #[test]
+    fn test_step_limit() {
+        // JT #1, #0
+        let agreement = differential(&[1105, 1, 0], &[], &backends(), 50).unwrap();
+        assert_eq!(agreement.steps, 50);
+        assert!(!agreement.finished);
+    }
+}
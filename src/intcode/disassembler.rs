@@ -0,0 +1,237 @@
+// Disassembler rendering Intcode programs as annotated assembly listings.
+//
+// Parameters are written `[a]` in position mode, `#a` in immediate mode and `[rb+a]` in relative
+// mode, e.g. `1002,4,3,4` is listed as `MUL [4], #3, [4]`.
+use super::{Instruction, Memory, Mode, Operation, Parameter, Word};
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+
+/// COMMENT_COLUMN is where the raw words of an instruction start in a listing line.
+const COMMENT_COLUMN: usize = 40;
+
+/// Line is a line of a disassembly listing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Line<W = i64> {
+    Instruction {
+        address: usize,
+        instruction: Instruction<W>,
+        /// The words the instruction is made of, as found in the program.
+        words: Vec<W>,
+    },
+    /// A word no reachable instruction starts at.
+    Data { address: usize, value: W },
+}
+
+impl<W: Word> fmt::Display for Parameter<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.mode {
+            Mode::Position => write!(f, "[{}]", self.value),
+            Mode::Immediate => write!(f, "#{}", self.value),
+            Mode::Relative => {
+                let offset = self.value.to_string();
+                if offset.starts_with('-') {
+                    write!(f, "[rb{}]", offset)
+                } else {
+                    write!(f, "[rb+{}]", offset)
+                }
+            }
+        }
+    }
+}
+
+impl<W: Word> fmt::Display for Instruction<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let operands: Vec<String> = self.parameters.iter().map(|p| p.to_string()).collect();
+        if operands.is_empty() {
+            write!(f, "{}", self.operation.mnemonic())
+        } else {
+            write!(f, "{:<4}{}", self.operation.mnemonic(), operands.join(", "))
+        }
+    }
+}
+
+impl<W: Word> fmt::Display for Line<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Line::Instruction {
+                address,
+                instruction,
+                words,
+            } => {
+                let code = format!("{:>5}: {}", address, instruction);
+                let words: Vec<String> = words.iter().map(|w| w.to_string()).collect();
+                write!(
+                    f,
+                    "{:<width$}; {}",
+                    code,
+                    words.join(","),
+                    width = COMMENT_COLUMN
+                )
+            }
+            Line::Data { address, value } => write!(f, "{:>5}: DATA {}", address, value),
+        }
+    }
+}
+
+/// successors returns the addresses execution can continue at after the instruction at
+/// `address`. Only jumps with an immediate target can be followed statically.
+pub fn successors<W: Word>(instruction: &Instruction<W>, address: usize) -> Vec<usize> {
+    let next = address + instruction.size();
+    let jump = |jump_if_zero: bool| {
+        let (condition, target) = (&instruction.parameters[0], &instruction.parameters[1]);
+        let target = match target.mode {
+            Mode::Immediate => target
+                .value
+                .to_i64()
+                .filter(|t| *t >= 0)
+                .map(|t| t as usize),
+            _ => None,
+        };
+        match condition.mode {
+            Mode::Immediate if condition.value.is_zero() == jump_if_zero => {
+                target.into_iter().collect()
+            }
+            Mode::Immediate => vec![next],
+            _ => std::iter::once(next).chain(target).collect(),
+        }
+    };
+    match instruction.operation {
+        Operation::Halt => vec![],
+        Operation::JumpIfTrue => jump(false),
+        Operation::JumpIfFalse => jump(true),
+        _ => vec![next],
+    }
+}
+
+/// reachable_instructions decodes every instruction reachable from the start of the program,
+/// following fall-throughs and immediate jumps.
+pub fn reachable_instructions<W: Word>(memory: &Memory<W>) -> BTreeMap<usize, Instruction<W>> {
+    let mut instructions: BTreeMap<usize, Instruction<W>> = BTreeMap::new();
+    let mut to_visit: VecDeque<usize> = VecDeque::from(vec![0]);
+    while let Some(address) = to_visit.pop_front() {
+        if address >= memory.len() || instructions.contains_key(&address) {
+            continue;
+        }
+        if let Ok(instruction) = Instruction::decode(memory, address) {
+            to_visit.extend(successors(&instruction, address));
+            instructions.insert(address, instruction);
+        }
+    }
+    instructions
+}
+
+/// disassemble splits a program into its reachable instructions and data words, in a linear
+/// sweep: a reachable instruction starting inside the words of a previous one is not listed, they
+/// overlap. An instruction cut off by the end of the program is listed as data.
+pub fn disassemble<W: Word>(program: &[W]) -> Vec<Line<W>> {
+    let memory = Memory::from(program);
+    let instructions = reachable_instructions(&memory);
+    let mut lines: Vec<Line<W>> = vec![];
+    let mut address = 0;
+    while address < program.len() {
+        match instructions.get(&address) {
+            Some(instruction) if address + instruction.size() <= program.len() => {
+                let end = address + instruction.size();
+                lines.push(Line::Instruction {
+                    address,
+                    instruction: instruction.clone(),
+                    words: (address..end).map(|a| memory.read(a)).collect(),
+                });
+                address = end;
+            }
+            _ => {
+                lines.push(Line::Data {
+                    address,
+                    value: program[address].clone(),
+                });
+                address += 1;
+            }
+        }
+    }
+    lines
+}
+
+/// listing returns the disassembly of a program, one line per instruction or data word.
+pub fn listing<W: Word>(program: &[W]) -> String {
+    disassemble(program)
+        .iter()
+        .map(|line| format!("{}\n", line))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::parse_program;
+
+    #[test]
+    fn test_parameter_display() {
+        let parameter = |mode, value: i64| Parameter { mode, value }.to_string();
+        assert_eq!(parameter(Mode::Position, 4), "[4]");
+        assert_eq!(parameter(Mode::Immediate, -3), "#-3");
+        assert_eq!(parameter(Mode::Relative, 2), "[rb+2]");
+        assert_eq!(parameter(Mode::Relative, -2), "[rb-2]");
+    }
+
+    #[test]
+    fn test_listing() {
+        let program: Vec<i64> = vec![1002, 4, 3, 4, 99, 7];
+        assert_eq!(
+            listing(&program),
+            "    0: MUL [4], #3, [4]                 ; 1002,4,3,4\n\
+             \x20   4: HLT                              ; 99\n\
+             \x20   5: DATA 7\n"
+        );
+    }
+
+    #[test]
+    fn test_truncated_instruction() {
+        let program: Vec<i64> = vec![1101, 1, 2];
+        assert_eq!(
+            listing(&program),
+            "    0: DATA 1101\n    1: DATA 1\n    2: DATA 2\n"
+        );
+    }
+
+    #[test]
+    fn test_unconditional_jump_skips_data() {
+        let program: Vec<i64> = vec![1105, 1, 4, 42, 204, -1, 99];
+        let lines = disassemble(&program);
+        assert_eq!(
+            lines[1],
+            Line::Data {
+                address: 3,
+                value: 42
+            }
+        );
+        assert_eq!(
+            lines[2].to_string().trim_end(),
+            "    4: OUT [rb-1]                       ; 204,-1"
+        );
+    }
+
+    #[test]
+    fn test_conditional_jump_follows_both_branches() {
+        let program: Vec<i64> = vec![1005, 9, 7, 104, 1, 99, 42, 104, 2, 99];
+        let addresses: Vec<usize> = reachable_instructions(&Memory::from(&program[..]))
+            .keys()
+            .copied()
+            .collect();
+        assert_eq!(addresses, vec![0, 3, 5, 7, 9]);
+    }
+
+    #[test]
+    fn test_day5_listing() {
+        let program: Vec<i64> = parse_program(include_str!("../../input/2019/day5.txt")).unwrap();
+        let lines = disassemble(&program);
+        assert_eq!(
+            lines[0].to_string().trim_end(),
+            "    0: IN  [225]                        ; 3,225"
+        );
+        assert_eq!(
+            lines[1].to_string().trim_end(),
+            "    2: ADD [225], [6], [6]              ; 1,225,6,6"
+        );
+        assert!(lines.iter().any(|line| matches!(line, Line::Data { .. })));
+    }
+}
@@ -0,0 +1,120 @@
+use std::error::Error;
+use std::fmt::{self, Debug, Display};
+use std::ops::Range;
+
+/// IntcodeError is what went wrong while executing the instruction at `address`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IntcodeError<W = i64> {
+    UnknownOpcode {
+        address: usize,
+        opcode: W,
+    },
+    InvalidParameterMode {
+        address: usize,
+        parameter: usize,
+        mode: i64,
+    },
+    /// A parameter, a jump or the relative base points before the start of the memory.
+    NegativeAddress {
+        address: usize,
+        target: i64,
+    },
+    /// A word too large to fit in an i64 was used as an address.
+    AddressOutOfRange {
+        address: usize,
+        target: W,
+    },
+    WriteInImmediateMode {
+        address: usize,
+        parameter: usize,
+    },
+    MissingInput {
+        address: usize,
+    },
+    Overflow {
+        address: usize,
+    },
+    /// The machine executed as many instructions as allowed, see Machine::limit_steps.
+    StepBudgetExceeded {
+        address: usize,
+        budget: usize,
+    },
+    /// The machine came back to the state it was in `period` steps before, without reading any
+    /// input, see Machine::detect_loops. `addresses` are the cells of the looping instructions.
+    InfiniteLoop {
+        address: usize,
+        addresses: Range<usize>,
+        period: usize,
+    },
+}
+
+impl<W> IntcodeError<W> {
+    /// address returns the address of the instruction which failed.
+    pub fn address(&self) -> usize {
+        match self {
+            IntcodeError::UnknownOpcode { address, .. }
+            | IntcodeError::InvalidParameterMode { address, .. }
+            | IntcodeError::NegativeAddress { address, .. }
+            | IntcodeError::AddressOutOfRange { address, .. }
+            | IntcodeError::WriteInImmediateMode { address, .. }
+            | IntcodeError::MissingInput { address }
+            | IntcodeError::Overflow { address }
+            | IntcodeError::StepBudgetExceeded { address, .. }
+            | IntcodeError::InfiniteLoop { address, .. } => *address,
+        }
+    }
+}
+
+impl<W: Display> Display for IntcodeError<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IntcodeError::UnknownOpcode { address, opcode } => {
+                write!(f, "unknown opcode {} at address {}", opcode, address)
+            }
+            IntcodeError::InvalidParameterMode {
+                address,
+                parameter,
+                mode,
+            } => write!(
+                f,
+                "invalid mode {} for parameter {} at address {}",
+                mode, parameter, address
+            ),
+            IntcodeError::NegativeAddress { address, target } => {
+                write!(f, "negative address {} used at address {}", target, address)
+            }
+            IntcodeError::AddressOutOfRange { address, target } => write!(
+                f,
+                "address {} used at address {} is out of range",
+                target, address
+            ),
+            IntcodeError::WriteInImmediateMode { address, parameter } => write!(
+                f,
+                "parameter {} at address {} is written to but is in immediate mode",
+                parameter, address
+            ),
+            IntcodeError::MissingInput { address } => {
+                write!(f, "no input available at address {}", address)
+            }
+            IntcodeError::Overflow { address } => {
+                write!(f, "integer overflow at address {}", address)
+            }
+            IntcodeError::StepBudgetExceeded { address, budget } => write!(
+                f,
+                "step budget of {} instructions exceeded at address {}",
+                budget, address
+            ),
+            IntcodeError::InfiniteLoop {
+                address,
+                addresses,
+                period,
+            } => write!(
+                f,
+                "infinite loop of {} steps over addresses {}..{} detected at address {}",
+                period, addresses.start, addresses.end, address
+            ),
+        }
+    }
+}
+
+impl<W: Debug + Display> Error for IntcodeError<W> {}
@@ -0,0 +1,173 @@
+// Properties of the Intcode machine checked on arbitrary programs, by the proptest generators of
+// the tests below and by the cargo-fuzz target in fuzz/ (run it with `cargo fuzz run intcode`):
+//
+// - the machine never panics, every instruction either runs or returns an IntcodeError;
+// - running a program instruction by instruction with Machine::step ends like Machine::run, with
+//   the interpreter as with the compiled program.
+//
+// Programs may loop forever: the properties are only compared on the ones ending within
+// MAX_STEPS instructions.
+//
+// The module is only built for the tests and with the `fuzzing` feature, which fuzz/ enables.
+use super::compiled::Compiled;
+use super::{IntcodeError, Machine};
+use std::collections::VecDeque;
+use std::convert::TryInto;
+use std::sync::Arc;
+
+/// MAX_STEPS is the step budget of a checked program.
+pub const MAX_STEPS: usize = 10_000;
+
+/// Ending is the state a program was left in.
+#[derive(Debug, PartialEq, Eq)]
+struct Ending<'a> {
+    result: Result<(), IntcodeError>,
+    outputs: Vec<i64>,
+    memory: &'a [i64],
+    ip: usize,
+    relative_base: i64,
+    halted: bool,
+}
+
+fn ending(machine: &Machine, result: Result<(), IntcodeError>, outputs: Vec<i64>) -> Ending<'_> {
+    Ending {
+        result,
+        outputs,
+        memory: machine.memory().as_slice(),
+        ip: machine.ip(),
+        relative_base: machine.relative_base(),
+        halted: machine.is_halted(),
+    }
+}
+
+/// check_program checks the properties on `program` reading `inputs`, and panics if one does
+/// not hold.
+pub fn check_program(program: &[i64], inputs: &[i64]) {
+    let mut stepped = Machine::new(program);
+    let mut queue = VecDeque::from(inputs.to_vec());
+    let mut outputs: Vec<i64> = vec![];
+    let mut result = Ok(());
+    let mut steps = 0;
+    while result.is_ok() && !stepped.is_halted() && stepped.ip() < stepped.memory().len() {
+        if steps == MAX_STEPS {
+            return;
+        }
+        result = stepped.step(&mut queue, &mut outputs);
+        steps += 1;
+    }
+    let expected = ending(&stepped, result, outputs);
+
+    let machines = vec![
+        ("interpreter", Machine::new(program)),
+        (
+            "compiled",
+            Machine::from_compiled(Arc::new(Compiled::new(program))),
+        ),
+    ];
+    for (name, mut machine) in machines {
+        let mut outputs: Vec<i64> = vec![];
+        let result = machine.run(&mut VecDeque::from(inputs.to_vec()), &mut outputs);
+        assert_eq!(
+            ending(&machine, result, outputs),
+            expected,
+            "run of the {} differs from step for {:?} with inputs {:?}",
+            name,
+            program,
+            inputs
+        );
+    }
+}
+
+/// check_bytes checks the properties on a program read from raw fuzzer data: its first byte is
+/// the number of inputs, then come the inputs and the program as little-endian i16 words, small
+/// enough to often be valid opcodes and addresses.
+pub fn check_bytes(data: &[u8]) {
+    let (count, data) = match data.split_first() {
+        Some((count, data)) => (*count as usize % 8, data),
+        None => return,
+    };
+    let words: Vec<i64> = data
+        .chunks_exact(2)
+        .map(|word| i64::from(i16::from_le_bytes(word.try_into().unwrap())))
+        .collect();
+    let (inputs, program) = words.split_at(count.min(words.len()));
+    check_program(program, inputs);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::{Mode, OPERATIONS};
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+
+    const MODES: [Mode; 3] = [Mode::Position, Mode::Immediate, Mode::Relative];
+
+    /// instruction generates a valid instruction whose parameters are small, so they mostly point
+    /// inside the program.
+    fn instruction() -> impl Strategy<Value = Vec<i64>> {
+        (
+            0..OPERATIONS.len(),
+            vec(0..MODES.len(), 3),
+            vec(-8i64..64, 3),
+        )
+            .prop_map(|(operation, modes, parameters)| {
+                let operation = OPERATIONS[operation];
+                let arguments = operation.get_args_expected();
+                let mut opcode = operation.opcode();
+                for (i, mode) in modes.iter().take(arguments).enumerate().rev() {
+                    let mode = match MODES[*mode] {
+                        Mode::Immediate if operation.is_write_parameter(i) => Mode::Position,
+                        mode => mode,
+                    };
+                    opcode += mode.digit() * 10i64.pow(i as u32 + 2);
+                }
+                let mut instruction = vec![opcode];
+                instruction.extend_from_slice(&parameters[..arguments]);
+                instruction
+            })
+    }
+
+    /// program generates well-formed programs: a sequence of instructions followed by data.
+    fn program() -> impl Strategy<Value = Vec<i64>> {
+        (vec(instruction(), 1..24), vec(-8i64..64, 0..8)).prop_map(|(instructions, data)| {
+            let mut program: Vec<i64> = instructions.into_iter().flatten().collect();
+            program.extend(data);
+            program
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn test_well_formed_programs(program in program(), inputs in vec(-4i64..64, 0..4)) {
+            check_program(&program, &inputs);
+        }
+
+        #[test]
+        fn test_arbitrary_words(
+            program in vec(prop_oneof![4 => -300i64..23000, 1 => any::<i64>()], 0..48),
+            inputs in vec(any::<i64>(), 0..4),
+        ) {
+            check_program(&program, &inputs);
+        }
+
+        #[test]
+        fn test_arbitrary_bytes(data in vec(any::<u8>(), 0..128)) {
+            check_bytes(&data);
+        }
+    }
+
+    #[test]
+    fn test_generated_instructions_are_valid() {
+        use crate::intcode::{Instruction, Memory};
+        use proptest::strategy::ValueTree;
+        use proptest::test_runner::TestRunner;
+
+        let mut runner = TestRunner::default();
+        for _ in 0..100 {
+            let instruction = instruction().new_tree(&mut runner).unwrap().current();
+            let decoded = Instruction::decode(&Memory::from(&instruction[..]), 0).unwrap();
+            assert_eq!(decoded.size(), instruction.len());
+        }
+    }
+}
@@ -0,0 +1,148 @@
+// Detection of infinite loops: a machine which comes back to a state it was in, without reading
+// any input in between, will repeat the same instructions forever.
+//
+// The states are compared with Brent's algorithm: one state is saved and compared with the
+// following ones, and replaced after 1, 2, 4, 8... steps. A loop of period λ is found after at
+// most a few times λ steps once the machine entered it, keeping a single copy of the memory.
+use super::{Input, Memory, Word};
+use std::ops::RangeInclusive;
+
+/// Loop is the cycle found by the detector: the addresses of the instructions executed in one
+/// period, and the number of steps in it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Loop {
+    pub(crate) starts: RangeInclusive<usize>,
+    pub(crate) period: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct State<W> {
+    ip: usize,
+    relative_base: i64,
+    memory: Memory<W>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct LoopDetector<W> {
+    saved: Option<State<W>>,
+    /// The number of steps since the state was saved, and after how many it is replaced.
+    since_saved: usize,
+    power: usize,
+    /// The lowest and highest addresses of the instructions executed since the state was saved.
+    starts: Option<(usize, usize)>,
+}
+
+/// Reads is an input remembering whether it was read from.
+pub(crate) struct Reads<'a, W> {
+    pub(crate) input: &'a mut dyn Input<W>,
+    pub(crate) read: bool,
+}
+
+impl<'a, W> Input<W> for Reads<'a, W> {
+    fn read(&mut self) -> Option<W> {
+        self.read = true;
+        self.input.read()
+    }
+}
+
+impl<W> Default for LoopDetector<W> {
+    fn default() -> Self {
+        LoopDetector {
+            saved: None,
+            since_saved: 0,
+            power: 1,
+            starts: None,
+        }
+    }
+}
+
+impl<W: Word> LoopDetector<W> {
+    /// observe records the step which executed the instruction at `address` and left the machine
+    /// in the given state. It returns the loop closed by the step, if any. A step reading an input
+    /// starts the search over, the following states may differ depending on the inputs.
+    pub(crate) fn observe(
+        &mut self,
+        address: usize,
+        read_input: bool,
+        ip: usize,
+        relative_base: i64,
+        memory: &Memory<W>,
+    ) -> Option<Loop> {
+        let state = || State {
+            ip,
+            relative_base,
+            memory: memory.clone(),
+        };
+        let saved = match &self.saved {
+            Some(saved) if !read_input => saved,
+            _ => {
+                *self = LoopDetector::default();
+                self.saved = Some(state());
+                return None;
+            }
+        };
+        self.since_saved += 1;
+        let (low, high) = match self.starts {
+            Some((low, high)) => (low.min(address), high.max(address)),
+            None => (address, address),
+        };
+        self.starts = Some((low, high));
+        if saved.ip == ip && saved.relative_base == relative_base && saved.memory == *memory {
+            return Some(Loop {
+                starts: low..=high,
+                period: self.since_saved,
+            });
+        }
+        if self.since_saved == self.power {
+            self.saved = Some(state());
+            self.since_saved = 0;
+            self.power *= 2;
+            self.starts = None;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_finds_the_period() {
+        let memory: Memory = Memory::from(&[99][..]);
+        let mut detector = LoopDetector::default();
+        // The instruction pointer goes 0, 1, 2, 3, 4, 5, 6, 4, 5, 6, 4...
+        let mut found = None;
+        for (step, address) in (0..7).chain((4..7).cycle()).take(100).enumerate() {
+            let ip = if address == 6 { 4 } else { address + 1 };
+            found = detector.observe(address, false, ip, 0, &memory);
+            if found.is_some() {
+                assert!(step < 20);
+                break;
+            }
+        }
+        assert_eq!(
+            found,
+            Some(Loop {
+                starts: 4..=6,
+                period: 3
+            })
+        );
+    }
+
+    #[test]
+    fn test_input_starts_over() {
+        let memory: Memory = Memory::from(&[99][..]);
+        let mut detector = LoopDetector::default();
+        for _ in 0..10 {
+            assert_eq!(detector.observe(0, true, 0, 0, &memory), None);
+        }
+        assert_eq!(
+            detector.observe(0, false, 0, 0, &memory),
+            Some(Loop {
+                starts: 0..=0,
+                period: 1
+            })
+        );
+    }
+}
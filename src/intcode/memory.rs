@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// DENSE_MEMORY_LIMIT is the first address stored in the sparse part of the memory.
+const DENSE_MEMORY_LIMIT: usize = 1 << 20;
+
+/// Memory is the memory of an Intcode machine. Every cell starts at its default value, 0 for
+/// words, and the memory grows as needed: addresses below DENSE_MEMORY_LIMIT live in a vector,
+/// the ones past it in a map so a program using a far away scratch cell does not allocate
+/// everything in between.
+///
+/// The vector is copied on write: clones of a memory share it until one of them writes to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Memory<W = i64> {
+    dense: Arc<Vec<W>>,
+    sparse: HashMap<usize, W>,
+}
+
+impl<W> Default for Memory<W> {
+    fn default() -> Self {
+        Memory {
+            dense: Arc::new(vec![]),
+            sparse: HashMap::new(),
+        }
+    }
+}
+
+impl<W: Clone> From<&[W]> for Memory<W> {
+    fn from(program: &[W]) -> Self {
+        Memory {
+            dense: Arc::new(Vec::from(program)),
+            sparse: HashMap::new(),
+        }
+    }
+}
+
+impl<W: Clone + Default + PartialEq> Memory<W> {
+    pub fn read(&self, address: usize) -> W {
+        let cell = if address < DENSE_MEMORY_LIMIT {
+            self.dense.get(address)
+        } else {
+            self.sparse.get(&address)
+        };
+        cell.cloned().unwrap_or_default()
+    }
+
+    pub fn write(&mut self, address: usize, value: W) {
+        if address < DENSE_MEMORY_LIMIT {
+            let dense = Arc::make_mut(&mut self.dense);
+            if address >= dense.len() {
+                dense.resize(address + 1, W::default());
+            }
+            dense[address] = value;
+        } else if value == W::default() {
+            self.sparse.remove(&address);
+        } else {
+            self.sparse.insert(address, value);
+        }
+    }
+
+    /// len returns the size of the dense part of the memory: the program and every cell up to the
+    /// highest address written below DENSE_MEMORY_LIMIT.
+    pub fn len(&self) -> usize {
+        self.dense.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dense.is_empty()
+    }
+
+    pub fn as_slice(&self) -> &[W] {
+        &self.dense
+    }
+
+    /// into_vec returns the dense part of the memory.
+    pub fn into_vec(self) -> Vec<W> {
+        Arc::try_unwrap(self.dense).unwrap_or_else(|dense| (*dense).clone())
+    }
+
+    /// sparse_cells returns the non-default cells past DENSE_MEMORY_LIMIT, by address.
+    pub fn sparse_cells(&self) -> Vec<(usize, W)> {
+        let mut cells: Vec<(usize, W)> = self
+            .sparse
+            .iter()
+            .map(|(address, value)| (*address, value.clone()))
+            .collect();
+        cells.sort_by_key(|(address, _)| *address);
+        cells
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_out_of_program() {
+        let memory: Memory = Memory::from(&[1, 2][..]);
+        assert_eq!(memory.read(1), 2);
+        assert_eq!(memory.read(2), 0);
+        assert_eq!(memory.read(DENSE_MEMORY_LIMIT * 4), 0);
+    }
+
+    #[test]
+    fn test_write_grows_memory() {
+        let mut memory: Memory = Memory::from(&[1, 2][..]);
+        memory.write(5, 7);
+        assert_eq!(memory.as_slice(), &[1, 2, 0, 0, 0, 7]);
+    }
+
+    #[test]
+    fn test_write_far_away_is_sparse() {
+        let mut memory: Memory = Memory::default();
+        memory.write(DENSE_MEMORY_LIMIT * 4, 7);
+        assert_eq!(memory.read(DENSE_MEMORY_LIMIT * 4), 7);
+        assert_eq!(memory.len(), 0);
+        memory.write(DENSE_MEMORY_LIMIT * 4, 0);
+        assert_eq!(memory, Memory::default());
+    }
+
+    #[test]
+    fn test_clone_is_copied_on_write() {
+        let mut memory: Memory = Memory::from(&[1, 2][..]);
+        let copy = memory.clone();
+        assert!(Arc::ptr_eq(&memory.dense, &copy.dense));
+        memory.write(0, 7);
+        assert!(!Arc::ptr_eq(&memory.dense, &copy.dense));
+        assert_eq!(memory.as_slice(), &[7, 2]);
+        assert_eq!(copy.as_slice(), &[1, 2]);
+    }
+}
@@ -0,0 +1,144 @@
+// Self-modifying code detection for the Intcode machine, see Machine::detect_code_writes.
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CodeWriteKind {
+    /// The written cell was part of an instruction executed before the write.
+    ExecutedCode,
+    /// The written cell became part of an instruction executed after the write.
+    LaterExecuted,
+}
+
+/// CodeWrite is a write performed by the instruction at `writer` into a cell executed as code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CodeWrite {
+    pub writer: usize,
+    pub target: usize,
+    pub kind: CodeWriteKind,
+}
+
+impl fmt::Display for CodeWrite {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            CodeWriteKind::ExecutedCode => write!(
+                f,
+                "instruction at {} wrote into executed code at {}",
+                self.writer, self.target
+            ),
+            CodeWriteKind::LaterExecuted => write!(
+                f,
+                "instruction at {} wrote {} which was executed later",
+                self.writer, self.target
+            ),
+        }
+    }
+}
+
+/// CodeWriteDetector follows the instructions executed by a machine and the cells they write to
+/// report the writes into code.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CodeWriteDetector {
+    /// The cells of every instruction executed so far.
+    executed: BTreeSet<usize>,
+    /// The cells written but not executed yet, with the address of their last writer.
+    written: BTreeMap<usize, usize>,
+    code_writes: Vec<CodeWrite>,
+}
+
+impl CodeWriteDetector {
+    /// code_writes returns the writes into code detected so far, in the order they were detected.
+    pub fn code_writes(&self) -> &[CodeWrite] {
+        &self.code_writes
+    }
+
+    /// record notes the execution of the `size` cells long instruction at `address`, which wrote
+    /// to `targets`.
+    pub(crate) fn record(&mut self, address: usize, size: usize, targets: &[usize]) {
+        for cell in address..address + size {
+            if let Some(writer) = self.written.remove(&cell) {
+                self.code_writes.push(CodeWrite {
+                    writer,
+                    target: cell,
+                    kind: CodeWriteKind::LaterExecuted,
+                });
+            }
+            self.executed.insert(cell);
+        }
+        for target in targets {
+            if self.executed.contains(target) {
+                self.code_writes.push(CodeWrite {
+                    writer: address,
+                    target: *target,
+                    kind: CodeWriteKind::ExecutedCode,
+                });
+            } else {
+                self.written.insert(*target, address);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::{parse_program, Machine};
+    use std::collections::VecDeque;
+
+    fn code_writes(program: &[i64], inputs: Vec<i64>) -> Vec<CodeWrite> {
+        let mut machine = Machine::new(program);
+        machine.detect_code_writes();
+        machine
+            .run(&mut VecDeque::from(inputs), &mut vec![])
+            .unwrap();
+        machine.code_writes().unwrap().to_vec()
+    }
+
+    #[test]
+    fn test_code_writes() {
+        // The multiplication turns its last operand into a HLT.
+        assert_eq!(
+            code_writes(&[1002, 4, 3, 4, 33], vec![]),
+            vec![CodeWrite {
+                writer: 0,
+                target: 4,
+                kind: CodeWriteKind::LaterExecuted,
+            }]
+        );
+        assert_eq!(
+            code_writes(&[1101, 1, 1, 3, 99], vec![]),
+            vec![CodeWrite {
+                writer: 0,
+                target: 3,
+                kind: CodeWriteKind::ExecutedCode,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_data_writes_are_ignored() {
+        assert_eq!(code_writes(&[3, 5, 4, 5, 99, 0], vec![7]), vec![]);
+    }
+
+    #[test]
+    fn test_code_write_display() {
+        let code_write = CodeWrite {
+            writer: 2,
+            target: 6,
+            kind: CodeWriteKind::LaterExecuted,
+        };
+        assert_eq!(
+            code_write.to_string(),
+            "instruction at 2 wrote 6 which was executed later"
+        );
+    }
+
+    #[test]
+    fn test_day5_patches_its_code() {
+        let program: Vec<i64> = parse_program(include_str!("../../input/2019/day5.txt")).unwrap();
+        let writes = code_writes(&program, vec![1]);
+        assert!(writes
+            .iter()
+            .any(|write| write.writer == 2 && write.target == 6));
+    }
+}
@@ -0,0 +1,306 @@
+// Network of Intcode machines exchanging packets.
+//
+// Every machine first reads its network address, then reads the packets sent to it as `x`, `y`
+// pairs, or -1 when none is waiting. It sends a packet by outputting its destination, `x` and `y`.
+// Packets sent to NAT_ADDRESS are kept by the NAT, which sends the last one to the machine 0 when
+// the whole network is idle.
+use super::compiled::Compiled;
+use super::{IntcodeError, Machine, NonBlockingInput};
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+
+pub const NAT_ADDRESS: usize = 255;
+/// QUANTUM is the maximum number of instructions a machine executes per turn.
+const QUANTUM: usize = 1000;
+const NO_PACKET: i64 = -1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Packet {
+    /// The address of the machine which sent the packet, NAT_ADDRESS for the NAT.
+    pub source: usize,
+    pub destination: usize,
+    pub x: i64,
+    pub y: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NetworkError {
+    Intcode {
+        node: usize,
+        error: IntcodeError,
+    },
+    UnknownDestination {
+        node: usize,
+        destination: i64,
+    },
+    /// The network is idle and the NAT has no packet to wake it up with.
+    Stalled,
+}
+
+impl fmt::Display for NetworkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NetworkError::Intcode { node, error } => write!(f, "node {} failed: {}", node, error),
+            NetworkError::UnknownDestination { node, destination } => write!(
+                f,
+                "node {} sent a packet to unknown address {}",
+                node, destination
+            ),
+            NetworkError::Stalled => write!(f, "the network is idle and the NAT has no packet"),
+        }
+    }
+}
+
+impl Error for NetworkError {}
+
+#[derive(Debug, Clone)]
+struct Node {
+    machine: Machine,
+    input: NonBlockingInput,
+    output: Vec<i64>,
+    /// Whether the node read from its empty queue without sending anything since it last
+    /// received a packet.
+    idle: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Network {
+    nodes: Vec<Node>,
+    nat: Option<Packet>,
+}
+
+impl Network {
+    /// new boots `size` machines running `program`, with the addresses 0 to size - 1.
+    pub fn new(program: &[i64], size: usize) -> Self {
+        let compiled = Arc::new(Compiled::new(program));
+        let nodes = (0..size)
+            .map(|address| {
+                let mut input = NonBlockingInput::new(NO_PACKET);
+                input.push(address as i64);
+                Node {
+                    machine: Machine::from_compiled(Arc::clone(&compiled)),
+                    input,
+                    output: vec![],
+                    idle: false,
+                }
+            })
+            .collect();
+        Network { nodes, nat: None }
+    }
+
+    /// nat returns the last packet received by the NAT.
+    pub fn nat(&self) -> Option<&Packet> {
+        self.nat.as_ref()
+    }
+
+    /// send delivers a packet to a machine, or to the NAT.
+    pub fn send(&mut self, packet: Packet) -> Result<(), NetworkError> {
+        if packet.destination == NAT_ADDRESS {
+            self.nat = Some(packet);
+            return Ok(());
+        }
+        let node =
+            self.nodes
+                .get_mut(packet.destination)
+                .ok_or(NetworkError::UnknownDestination {
+                    node: packet.source,
+                    destination: packet.destination as i64,
+                })?;
+        node.input.push(packet.x);
+        node.input.push(packet.y);
+        node.idle = false;
+        Ok(())
+    }
+
+    /// is_idle tells if every machine is waiting for a packet, or halted: the packets sent to a
+    /// halted machine are never read.
+    fn is_idle(&self) -> bool {
+        self.nodes
+            .iter()
+            .all(|node| node.machine.is_halted() || (node.idle && node.input.is_empty()))
+    }
+
+    /// round gives a turn to every machine, and wakes the network up with the packet of the NAT
+    /// if it is idle afterwards. It returns the packets sent during the round, in order.
+    pub fn round(&mut self) -> Result<Vec<Packet>, NetworkError> {
+        let mut packets: Vec<Packet> = vec![];
+        for address in 0..self.nodes.len() {
+            for packet in self.turn(address)? {
+                self.send(packet)?;
+                packets.push(packet);
+            }
+        }
+        if self.is_idle() {
+            let packet = self.nat.ok_or(NetworkError::Stalled)?;
+            let wake_up = Packet {
+                source: NAT_ADDRESS,
+                destination: 0,
+                ..packet
+            };
+            self.send(wake_up)?;
+            packets.push(wake_up);
+        }
+        Ok(packets)
+    }
+
+    /// turn runs a machine until it reads from its empty queue, halts or executes QUANTUM
+    /// instructions, and returns the packets it sent.
+    fn turn(&mut self, address: usize) -> Result<Vec<Packet>, NetworkError> {
+        let node = &mut self.nodes[address];
+        let empty_reads = node.input.empty_reads();
+        let mut packets: Vec<Packet> = vec![];
+        for _ in 0..QUANTUM {
+            if node.machine.is_halted() || node.input.empty_reads() > empty_reads {
+                break;
+            }
+            node.machine
+                .step(&mut node.input, &mut node.output)
+                .map_err(|error| NetworkError::Intcode {
+                    node: address,
+                    error,
+                })?;
+            if let [destination, x, y] = node.output[..] {
+                node.output.clear();
+                if destination < 0 {
+                    return Err(NetworkError::UnknownDestination {
+                        node: address,
+                        destination,
+                    });
+                }
+                packets.push(Packet {
+                    source: address,
+                    destination: destination as usize,
+                    x,
+                    y,
+                });
+            }
+        }
+        node.idle = packets.is_empty() && node.input.empty_reads() > empty_reads;
+        Ok(packets)
+    }
+
+    /// first_nat_packet runs the network until a packet is sent to the NAT.
+    pub fn first_nat_packet(&mut self) -> Result<Packet, NetworkError> {
+        loop {
+            let packets = self.round()?;
+            if let Some(packet) = packets.iter().find(|p| p.destination == NAT_ADDRESS) {
+                return Ok(*packet);
+            }
+        }
+    }
+
+    /// first_repeated_wake_up runs the network until the NAT wakes it up twice in a row with the
+    /// same `y` value, and returns that value.
+    pub fn first_repeated_wake_up(&mut self) -> Result<i64, NetworkError> {
+        let mut last: Option<i64> = None;
+        loop {
+            for packet in self.round()? {
+                if packet.source != NAT_ADDRESS {
+                    continue;
+                }
+                if last == Some(packet.y) {
+                    return Ok(packet.y);
+                }
+                last = Some(packet.y);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::assembler::assemble;
+
+    /// relay is a node forwarding to the NAT every packet it receives, with `x` increased by its
+    /// address.
+    fn relay() -> Vec<i64> {
+        assemble(
+            "
+                      IN  [address]
+            receive:  IN  [x]
+                      EQ  [x], #-1, [empty]
+                      JT  [empty], #receive
+                      IN  [y]
+                      ADD [x], [address], [x]
+                      OUT #255
+                      OUT [x]
+                      OUT [y]
+                      JT  #1, #receive
+            address:  DATA 0
+            x:        DATA 0
+            y:        DATA 0
+            empty:    DATA 0
+            ",
+        )
+        .unwrap()
+    }
+
+    fn packet(source: usize, destination: usize, x: i64, y: i64) -> Packet {
+        Packet {
+            source,
+            destination,
+            x,
+            y,
+        }
+    }
+
+    #[test]
+    fn test_first_nat_packet() {
+        let mut network = Network::new(&relay(), 50);
+        network.send(packet(0, 42, 1, 2)).unwrap();
+        assert_eq!(network.first_nat_packet(), Ok(packet(42, 255, 43, 2)));
+    }
+
+    #[test]
+    fn test_repeated_wake_up() {
+        let mut network = Network::new(&relay(), 50);
+        network.send(packet(0, 42, 1, 2)).unwrap();
+        assert_eq!(network.first_repeated_wake_up(), Ok(2));
+        assert_eq!(network.nat(), Some(&packet(0, 255, 43, 2)));
+    }
+
+    #[test]
+    fn test_stalled_network() {
+        let mut network = Network::new(&relay(), 50);
+        assert_eq!(network.first_nat_packet(), Err(NetworkError::Stalled));
+    }
+
+    #[test]
+    fn test_packet_to_halted_node() {
+        // Node 0 sends a packet to node 1, which halted, and one to the NAT, then polls forever.
+        let program = assemble(
+            "
+                     IN  [address]
+                     JT  [address], #halt
+                     OUT #1
+                     OUT #5
+                     OUT #7
+                     OUT #255
+                     OUT #0
+                     OUT #3
+            poll:    IN  [x]
+                     JT  #1, #poll
+            halt:    HLT
+            address: DATA 0
+            x:       DATA 0
+            ",
+        )
+        .unwrap();
+        let mut network = Network::new(&program, 2);
+        assert_eq!(network.first_repeated_wake_up(), Ok(3));
+    }
+
+    #[test]
+    fn test_unknown_destination() {
+        let mut network = Network::new(&[104, 50, 104, 0, 104, 0, 99], 2);
+        assert_eq!(
+            network.round(),
+            Err(NetworkError::UnknownDestination {
+                node: 0,
+                destination: 50
+            })
+        );
+    }
+}
@@ -0,0 +1,278 @@
+// Robots on a 2D grid controlled by an Intcode program.
+//
+// Two protocols are supported:
+// - painting (Robot::paint): the program reads the color of the panel under the robot and outputs
+//   the color to paint it with, then 0 to turn left or 1 to turn right, before moving forward.
+// - driving (Robot::drive): the program reads a movement command, 1 to 4 for north, south, west and
+//   east, and outputs a status, 0 when a wall blocked the robot.
+pub use crate::day3::{Direction, Position};
+
+use super::{Event, IntcodeError, Machine};
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+
+pub const BLACK: i64 = 0;
+pub const WHITE: i64 = 1;
+const TURN_LEFT: i64 = 0;
+const TURN_RIGHT: i64 = 1;
+pub const WALL: i64 = 0;
+
+/// Grid is a map of the cells of a 2D world, the cells never set being unknown.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grid<T> {
+    cells: HashMap<Position, T>,
+}
+
+impl<T> Default for Grid<T> {
+    fn default() -> Self {
+        Grid {
+            cells: HashMap::new(),
+        }
+    }
+}
+
+impl<T> Grid<T> {
+    pub fn get(&self, position: Position) -> Option<&T> {
+        self.cells.get(&position)
+    }
+
+    pub fn set(&mut self, position: Position, value: T) {
+        self.cells.insert(position, value);
+    }
+
+    /// len returns the number of known cells.
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    /// render draws the smallest rectangle holding every known cell, one character per cell and
+    /// the highest row first.
+    pub fn render<F: Fn(Option<&T>) -> char>(&self, cell: F) -> String {
+        let xs = self.cells.keys().map(|p| p.x);
+        let ys = self.cells.keys().map(|p| p.y);
+        let (min_x, max_x) = (xs.clone().min().unwrap_or(0), xs.max().unwrap_or(-1));
+        let (min_y, max_y) = (ys.clone().min().unwrap_or(0), ys.max().unwrap_or(-1));
+        let mut drawing = String::new();
+        for y in (min_y..=max_y).rev() {
+            for x in min_x..=max_x {
+                drawing.push(cell(self.get(Position { x, y })));
+            }
+            drawing.push('\n');
+        }
+        drawing
+    }
+}
+
+impl Grid<char> {
+    /// parse reads a drawing, like the ASCII views of a camera, the first line being the row 0 and
+    /// the following ones going down.
+    pub fn parse(drawing: &str) -> Self {
+        let mut grid = Grid::default();
+        for (row, line) in drawing.lines().enumerate() {
+            for (column, c) in line.chars().enumerate() {
+                let position = Position {
+                    x: column as i32,
+                    y: -(row as i32),
+                };
+                grid.set(position, c);
+            }
+        }
+        grid
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RobotError {
+    Intcode(IntcodeError),
+    /// The program output a value the protocol does not know.
+    InvalidOutput(i64),
+    /// The program halted or waited for an input instead of outputting a value.
+    MissingOutput,
+}
+
+impl fmt::Display for RobotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RobotError::Intcode(error) => write!(f, "{}", error),
+            RobotError::InvalidOutput(value) => write!(f, "invalid output {}", value),
+            RobotError::MissingOutput => write!(f, "the program stopped without output"),
+        }
+    }
+}
+
+impl Error for RobotError {}
+
+impl From<IntcodeError> for RobotError {
+    fn from(error: IntcodeError) -> Self {
+        RobotError::Intcode(error)
+    }
+}
+
+/// Robot is a robot moving on a grid, starting at the origin and facing up.
+#[derive(Debug, Clone)]
+pub struct Robot {
+    machine: Machine,
+    position: Position,
+    direction: Direction,
+    visited: HashSet<Position>,
+    grid: Grid<i64>,
+}
+
+impl Robot {
+    pub fn new(program: &[i64]) -> Self {
+        let origin = Position { x: 0, y: 0 };
+        Robot {
+            machine: Machine::new(program),
+            position: origin,
+            direction: Direction::Up,
+            visited: vec![origin].into_iter().collect(),
+            grid: Grid::default(),
+        }
+    }
+
+    pub fn position(&self) -> Position {
+        self.position
+    }
+
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    /// visited returns every position the robot has been at.
+    pub fn visited(&self) -> &HashSet<Position> {
+        &self.visited
+    }
+
+    /// grid returns the painted panels, or the statuses of the cells the robot tried to move to.
+    pub fn grid(&self) -> &Grid<i64> {
+        &self.grid
+    }
+
+    pub fn grid_mut(&mut self) -> &mut Grid<i64> {
+        &mut self.grid
+    }
+
+    fn next_output(&mut self) -> Result<i64, RobotError> {
+        match self.machine.run_until_event()? {
+            Event::Output(value) => Ok(value),
+            Event::NeedsInput | Event::Halted => Err(RobotError::MissingOutput),
+        }
+    }
+
+    fn move_to(&mut self, position: Position) {
+        self.position = position;
+        self.visited.insert(position);
+    }
+
+    /// paint runs the painting protocol until the program halts. Panels never painted are black.
+    pub fn paint(&mut self) -> Result<(), RobotError> {
+        loop {
+            match self.machine.run_until_event()? {
+                Event::Halted => return Ok(()),
+                Event::NeedsInput => {
+                    let color = *self.grid.get(self.position).unwrap_or(&BLACK);
+                    self.machine.push_input(color);
+                }
+                Event::Output(color) => {
+                    self.grid.set(self.position, color);
+                    self.direction = match self.next_output()? {
+                        TURN_LEFT => self.direction.turn_left(),
+                        TURN_RIGHT => self.direction.turn_right(),
+                        value => return Err(RobotError::InvalidOutput(value)),
+                    };
+                    self.move_to(self.position.moved(self.direction));
+                }
+            }
+        }
+    }
+
+    /// drive sends a movement command following the driving protocol and returns the status output
+    /// by the program, which is also recorded in the grid for the cell the robot tried to reach.
+    pub fn drive(&mut self, direction: Direction) -> Result<i64, RobotError> {
+        let command = match direction {
+            Direction::Up => 1,
+            Direction::Down => 2,
+            Direction::Left => 3,
+            Direction::Right => 4,
+        };
+        self.machine.push_input(command);
+        let status = self.next_output()?;
+        let target = self.position.moved(direction);
+        self.grid.set(target, status);
+        self.direction = direction;
+        if status != WALL {
+            self.move_to(target);
+        }
+        Ok(status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::assembler::assemble;
+
+    #[test]
+    fn test_paint() {
+        // The robot of the example of https://adventofcode.com/2019/day/11
+        let moves = [(1, 0), (0, 0), (1, 0), (1, 0), (0, 1), (1, 0), (1, 0)];
+        let source: String = moves
+            .iter()
+            .map(|(color, turn)| format!("IN [99]\nOUT #{}\nOUT #{}\n", color, turn))
+            .chain(std::iter::once(String::from("HLT\n")))
+            .collect();
+        let mut robot = Robot::new(&assemble(&source).unwrap());
+        robot.paint().unwrap();
+        assert_eq!(robot.grid().len(), 6);
+        assert_eq!(robot.position(), Position { x: 0, y: 1 });
+        assert_eq!(robot.direction(), Direction::Left);
+        let drawing = robot.grid().render(|cell| match cell {
+            Some(&WHITE) => '#',
+            _ => '.',
+        });
+        assert_eq!(drawing, "..#\n..#\n##.\n");
+    }
+
+    #[test]
+    fn test_drive() {
+        // A droid in a corridor whose north is a wall.
+        let program = assemble(
+            "
+            loop:     IN  [command]
+                      EQ  [command], #1, [blocked]
+                      JT  [blocked], #wall
+                      OUT #1
+                      JT  #1, #loop
+            wall:     OUT #0
+                      JT  #1, #loop
+            command:  DATA 0
+            blocked:  DATA 0
+            ",
+        )
+        .unwrap();
+        let mut robot = Robot::new(&program);
+        assert_eq!(robot.drive(Direction::Up), Ok(WALL));
+        assert_eq!(robot.position(), Position { x: 0, y: 0 });
+        assert_eq!(robot.drive(Direction::Right), Ok(1));
+        assert_eq!(robot.position(), Position { x: 1, y: 0 });
+        assert_eq!(robot.visited().len(), 2);
+        let drawing = robot.grid().render(|cell| match cell {
+            Some(&WALL) => '#',
+            Some(_) => '.',
+            None => ' ',
+        });
+        assert_eq!(drawing, "# \n .\n");
+    }
+
+    #[test]
+    fn test_parse_grid() {
+        let grid = Grid::parse("#.\n.^");
+        assert_eq!(grid.get(Position { x: 1, y: -1 }), Some(&'^'));
+        assert_eq!(grid.render(|cell| *cell.unwrap_or(&' ')), "#.\n.^\n");
+    }
+}
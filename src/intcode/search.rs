@@ -0,0 +1,432 @@
+// Search of the values to patch into a program for it to leave a target value in memory, like
+// the noun and verb of https://adventofcode.com/2019/day/2.
+use super::compiled::Compiled;
+use super::{IntcodeError, Machine};
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+/// STEP_BUDGET is a step budget large enough for the programs of the puzzles.
+pub const STEP_BUDGET: usize = 10_000_000;
+
+/// Unknown is a cell of the program to patch, with the values it can take.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unknown {
+    pub address: usize,
+    pub values: RangeInclusive<i64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    /// Try every candidate in order.
+    Sequential,
+    /// Try the candidates on every core.
+    Parallel,
+    /// Check whether the output is linear in the unknowns and solve the equation directly,
+    /// searching sequentially if it is not. The check only runs a few candidates, so an output
+    /// which merely looks linear on them may give a solution other than the first one.
+    Linear,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SearchError {
+    /// The program failed when run with `values`.
+    Intcode {
+        values: Vec<i64>,
+        error: IntcodeError,
+    },
+    NotFound,
+    /// There are more candidates than can be counted.
+    TooManyCandidates,
+}
+
+impl fmt::Display for SearchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SearchError::Intcode { values, error } => {
+                write!(f, "the program failed with {:?}: {}", values, error)
+            }
+            SearchError::NotFound => write!(f, "no values produce the target output"),
+            SearchError::TooManyCandidates => write!(f, "too many values to search"),
+        }
+    }
+}
+
+impl Error for SearchError {}
+
+/// Search looks for the values of the unknowns of a program leaving `target` at `output`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Search<'a> {
+    pub program: &'a [i64],
+    pub unknowns: Vec<Unknown>,
+    pub output: usize,
+    pub target: i64,
+    /// The number of instructions a run may execute: a candidate looping forever fails with
+    /// StepBudgetExceeded instead of blocking the search.
+    pub step_budget: usize,
+}
+
+impl<'a> Search<'a> {
+    /// evaluate runs the program with the unknowns set to `values` and returns the output cell.
+    pub fn evaluate(&self, values: &[i64]) -> Result<i64, SearchError> {
+        self.run(&Arc::new(Compiled::new(self.program)), values)
+    }
+
+    fn run(&self, compiled: &Arc<Compiled>, values: &[i64]) -> Result<i64, SearchError> {
+        let mut machine = Machine::from_compiled(Arc::clone(compiled));
+        machine.limit_steps(self.step_budget);
+        for (unknown, value) in self.unknowns.iter().zip(values) {
+            machine.write(unknown.address, *value);
+        }
+        machine
+            .run(&mut VecDeque::new(), &mut vec![])
+            .map_err(|error| SearchError::Intcode {
+                values: values.to_vec(),
+                error,
+            })?;
+        Ok(machine.read(self.output))
+    }
+
+    fn candidates(&self) -> Result<usize, SearchError> {
+        self.unknowns
+            .iter()
+            .try_fold(1usize, |candidates, unknown| {
+                range_len(&unknown.values).and_then(|len| candidates.checked_mul(len))
+            })
+            .ok_or(SearchError::TooManyCandidates)
+    }
+
+    /// candidate returns the values of the candidate `index`, the last unknown varying fastest.
+    /// The number of candidates must have been counted, see candidates.
+    fn candidate(&self, mut index: usize) -> Vec<i64> {
+        let mut values: Vec<i64> = vec![0; self.unknowns.len()];
+        for (i, unknown) in self.unknowns.iter().enumerate().rev() {
+            let len = range_len(&unknown.values).unwrap();
+            // The offset may not fit in an i64, but the value does.
+            values[i] = unknown.values.start().wrapping_add((index % len) as i64);
+            index /= len;
+        }
+        values
+    }
+
+    /// solve returns the values of the first candidate leaving the target at the output, in the
+    /// order of the nested loops over the unknowns, the last one innermost. Strategy::Linear may
+    /// return another solution, see its documentation.
+    pub fn solve(&self, strategy: Strategy) -> Result<Vec<i64>, SearchError> {
+        let candidates = self.candidates()?;
+        let compiled = Arc::new(Compiled::new(self.program));
+        match strategy {
+            Strategy::Sequential => self.solve_sequential(&compiled, candidates),
+            Strategy::Parallel => self.solve_parallel(&compiled, candidates),
+            Strategy::Linear => match self.solve_linear(&compiled, candidates) {
+                Some(values) => Ok(values),
+                None => self.solve_sequential(&compiled, candidates),
+            },
+        }
+    }
+
+    fn solve_sequential(
+        &self,
+        compiled: &Arc<Compiled>,
+        candidates: usize,
+    ) -> Result<Vec<i64>, SearchError> {
+        for index in 0..candidates {
+            let values = self.candidate(index);
+            if self.run(compiled, &values)? == self.target {
+                return Ok(values);
+            }
+        }
+        Err(SearchError::NotFound)
+    }
+
+    /// solve_parallel splits the candidates between the cores. A thread stops once a candidate
+    /// before its current one succeeded or failed, so the result is the one of solve_sequential.
+    fn solve_parallel(
+        &self,
+        compiled: &Arc<Compiled>,
+        candidates: usize,
+    ) -> Result<Vec<i64>, SearchError> {
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        let first = AtomicUsize::new(candidates);
+        let outcomes: Vec<(usize, Result<Vec<i64>, SearchError>)> = thread::scope(|scope| {
+            let handles: Vec<_> = (0..threads)
+                .map(|thread| {
+                    let first = &first;
+                    scope.spawn(move || {
+                        for index in (thread..candidates).step_by(threads) {
+                            if index > first.load(Ordering::Relaxed) {
+                                break;
+                            }
+                            let values = self.candidate(index);
+                            let outcome = match self.run(compiled, &values) {
+                                Ok(output) if output == self.target => Ok(values),
+                                Ok(_) => continue,
+                                Err(error) => Err(error),
+                            };
+                            first.fetch_min(index, Ordering::Relaxed);
+                            return Some((index, outcome));
+                        }
+                        None
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .filter_map(|handle| handle.join().unwrap())
+                .collect()
+        });
+        outcomes
+            .into_iter()
+            .min_by_key(|(index, _)| *index)
+            .map_or(Err(SearchError::NotFound), |(_, outcome)| outcome)
+    }
+
+    /// solve_linear computes the output as `c + a0 * x0 + a1 * x1 + ...` from a few runs, checks
+    /// the model on the last candidate and solves it for the last unknown. It returns None when
+    /// the model does not apply, for the search to go on sequentially: the output is not linear in
+    /// the unknowns, a run failed, or no solution was found.
+    fn solve_linear(&self, compiled: &Arc<Compiled>, candidates: usize) -> Option<Vec<i64>> {
+        if candidates == 0 {
+            return None;
+        }
+        let evaluate = |values: &[i64]| self.run(compiled, values).ok().map(i128::from);
+        let base = self.candidate(0);
+        let constant = evaluate(&base)?;
+        let mut coefficients: Vec<i128> = vec![];
+        for (i, unknown) in self.unknowns.iter().enumerate() {
+            if range_len(&unknown.values) == Some(1) {
+                coefficients.push(0);
+                continue;
+            }
+            let mut values = base.clone();
+            values[i] += 1;
+            coefficients.push(evaluate(&values)? - constant);
+        }
+        let predict = |values: &[i64]| -> Option<i128> {
+            values.iter().zip(&base).zip(&coefficients).try_fold(
+                constant,
+                |sum, ((value, start), a)| {
+                    a.checked_mul(i128::from(*value) - i128::from(*start))
+                        .and_then(|term| sum.checked_add(term))
+                },
+            )
+        };
+        let last = self.candidate(candidates - 1);
+        if evaluate(&last)? != predict(&last)? {
+            return None;
+        }
+        let (unknown, coefficient) = (self.unknowns.last()?, *coefficients.last()?);
+        let stride = range_len(&unknown.values)?;
+        for index in (0..candidates).step_by(stride) {
+            let mut values = self.candidate(index);
+            let missing = i128::from(self.target) - predict(&values)?;
+            let delta = match coefficient {
+                0 if missing == 0 => 0,
+                0 => continue,
+                a if missing % a == 0 => missing / a,
+                _ => continue,
+            };
+            if delta < 0 || delta >= stride as i128 {
+                continue;
+            }
+            let last = values.last_mut()?;
+            *last = last.wrapping_add(delta as i64);
+            // The model was only checked on a few candidates, the solution is confirmed by a run.
+            if evaluate(&values)? != i128::from(self.target) {
+                return None;
+            }
+            return Some(values);
+        }
+        None
+    }
+}
+
+/// range_len returns the number of values in `range`, None if it does not fit in a usize.
+fn range_len(range: &RangeInclusive<i64>) -> Option<usize> {
+    if range.is_empty() {
+        return Some(0);
+    }
+    usize::try_from(i128::from(*range.end()) - i128::from(*range.start()) + 1).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::parse_program;
+
+    fn day2_search(program: &[i64], target: i64) -> Search<'_> {
+        Search {
+            program,
+            unknowns: vec![
+                Unknown {
+                    address: 1,
+                    values: 0..=99,
+                },
+                Unknown {
+                    address: 2,
+                    values: 0..=99,
+                },
+            ],
+            output: 0,
+            target,
+            step_budget: STEP_BUDGET,
+        }
+    }
+
+    #[test]
+    fn test_strategies_agree() {
+        let program: Vec<i64> = parse_program(include_str!("../../input/2019/day2.txt")).unwrap();
+        let search = day2_search(&program, 19_690_720);
+        for strategy in &[Strategy::Sequential, Strategy::Parallel, Strategy::Linear] {
+            assert_eq!(search.solve(*strategy), Ok(vec![72, 64]));
+        }
+
+        // The last candidate fails on an input, the linear model does not apply.
+        let program = vec![0, 0, 0, 0, 99];
+        let search = Search {
+            unknowns: vec![Unknown {
+                address: 0,
+                values: 1..=3,
+            }],
+            ..day2_search(&program, 2)
+        };
+        for strategy in &[Strategy::Sequential, Strategy::Parallel, Strategy::Linear] {
+            assert_eq!(search.solve(*strategy), Ok(vec![1]));
+        }
+    }
+
+    #[test]
+    fn test_not_found() {
+        let program: Vec<i64> = parse_program(include_str!("../../input/2019/day2.txt")).unwrap();
+        let search = day2_search(&program, -1);
+        for strategy in &[Strategy::Sequential, Strategy::Parallel, Strategy::Linear] {
+            assert_eq!(search.solve(*strategy), Err(SearchError::NotFound));
+        }
+    }
+
+    #[test]
+    fn test_non_linear_output() {
+        // [0] = [5] * [6]
+        let program = vec![2, 5, 6, 0, 99, 0, 0];
+        let search = Search {
+            program: &program,
+            unknowns: vec![
+                Unknown {
+                    address: 5,
+                    values: 1..=9,
+                },
+                Unknown {
+                    address: 6,
+                    values: 1..=9,
+                },
+            ],
+            output: 0,
+            target: 42,
+            step_budget: STEP_BUDGET,
+        };
+        assert_eq!(search.solve(Strategy::Linear), Ok(vec![6, 7]));
+        assert_eq!(search.solve(Strategy::Parallel), Ok(vec![6, 7]));
+
+        // [0] = x * (x - 1) * (x - 3), which looks constant on 0, 1 and 3.
+        let program = vec![
+            1001, 17, -1, 18, 1001, 17, -3, 19, 2, 17, 18, 18, 2, 18, 19, 0, 99, 0, 0, 0,
+        ];
+        let search = Search {
+            unknowns: vec![Unknown {
+                address: 17,
+                values: 0..=3,
+            }],
+            ..day2_search(&program, -2)
+        };
+        for strategy in &[Strategy::Sequential, Strategy::Parallel, Strategy::Linear] {
+            assert_eq!(search.solve(*strategy), Ok(vec![2]));
+        }
+    }
+
+    #[test]
+    fn test_too_many_candidates() {
+        let program = vec![99];
+        let unknown = |address| Unknown {
+            address,
+            values: 0..=i64::from(u32::MAX),
+        };
+        let searches = vec![
+            Search {
+                unknowns: vec![Unknown {
+                    address: 0,
+                    values: i64::MIN..=i64::MAX,
+                }],
+                ..day2_search(&program, 0)
+            },
+            Search {
+                unknowns: vec![unknown(1), unknown(2)],
+                ..day2_search(&program, 0)
+            },
+        ];
+        for search in &searches {
+            for strategy in &[Strategy::Sequential, Strategy::Parallel, Strategy::Linear] {
+                assert_eq!(search.solve(*strategy), Err(SearchError::TooManyCandidates));
+            }
+        }
+        // The widest range which can be counted.
+        let search = Search {
+            unknowns: vec![Unknown {
+                address: 1,
+                values: i64::MIN..=i64::MAX - 1,
+            }],
+            ..day2_search(&[1101, 0, 0, 0, 99], i64::MIN)
+        };
+        assert_eq!(search.solve(Strategy::Linear), Ok(vec![i64::MIN]));
+    }
+
+    #[test]
+    fn test_looping_candidate() {
+        // JT #0, #0 goes on to HLT, JF #0, #0 loops forever.
+        let program = vec![0, 0, 0, 99];
+        let search = Search {
+            unknowns: vec![Unknown {
+                address: 0,
+                values: 1105..=1106,
+            }],
+            step_budget: 1000,
+            ..day2_search(&program, 0)
+        };
+        let expected = Err(SearchError::Intcode {
+            values: vec![1106],
+            error: IntcodeError::StepBudgetExceeded {
+                address: 0,
+                budget: 1000,
+            },
+        });
+        for strategy in &[Strategy::Sequential, Strategy::Parallel, Strategy::Linear] {
+            assert_eq!(search.solve(*strategy), expected);
+        }
+    }
+
+    #[test]
+    fn test_intcode_error() {
+        let program = vec![1, 0, 0, 0, 99];
+        let search = Search {
+            unknowns: vec![Unknown {
+                address: 0,
+                values: 97..=99,
+            }],
+            ..day2_search(&program, 0)
+        };
+        assert_eq!(
+            search.solve(Strategy::Parallel),
+            Err(SearchError::Intcode {
+                values: vec![97],
+                error: IntcodeError::UnknownOpcode {
+                    address: 0,
+                    opcode: 97
+                }
+            })
+        );
+    }
+}
@@ -0,0 +1,292 @@
+// Snapshots of the state of a machine, to resume it later or from several points of a search.
+//
+// A snapshot is saved as text, one `key: value` line per field after a version header:
+//
+//     intcode-snapshot 1
+//     ip: 12
+//     relative-base: 2000
+//     halted: false
+//     inputs: 3,-1
+//     memory: 109,1,203,-1
+//     sparse: 4194304=7
+//
+// `inputs` are the values pushed with Machine::push_input and not read yet, `memory` is the dense
+// part of the memory (see Memory::as_slice) and `sparse` lists its other non-zero cells as
+// `address=value`. Lists are separated by commas and may be empty. The fields can come in any
+// order but must all be there, blank lines are ignored.
+use super::{Memory, Word};
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+const HEADER: &str = "intcode-snapshot 1";
+const FIELDS: [&str; 6] = [
+    "ip",
+    "relative-base",
+    "halted",
+    "inputs",
+    "memory",
+    "sparse",
+];
+
+/// Snapshot is everything needed to resume a machine: its memory, its registers and the inputs
+/// pushed but not read yet. Taking one is cheap, the memory is copied on write.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot<W = i64> {
+    pub memory: Memory<W>,
+    pub ip: usize,
+    pub relative_base: i64,
+    pub halted: bool,
+    pub inputs: VecDeque<W>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotError {
+    /// The text does not start with the header of a supported version.
+    UnsupportedVersion(String),
+    MissingField(&'static str),
+    UnknownField(String),
+    DuplicateField(String),
+    InvalidValue {
+        field: String,
+        value: String,
+    },
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SnapshotError::UnsupportedVersion(header) => {
+                write!(f, "unsupported snapshot header {:?}", header)
+            }
+            SnapshotError::MissingField(field) => write!(f, "missing field {}", field),
+            SnapshotError::UnknownField(field) => write!(f, "unknown field {}", field),
+            SnapshotError::DuplicateField(field) => write!(f, "duplicate field {}", field),
+            SnapshotError::InvalidValue { field, value } => {
+                write!(f, "invalid value {:?} for field {}", value, field)
+            }
+        }
+    }
+}
+
+impl Error for SnapshotError {}
+
+impl<W: Word> Snapshot<W> {
+    /// save writes the snapshot to the file at `path`.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+
+    /// load reads a snapshot written by save.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        fs::read_to_string(path)?
+            .parse()
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+}
+
+fn join<T: fmt::Display>(values: impl Iterator<Item = T>) -> String {
+    values
+        .map(|value| value.to_string())
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+impl<W: Word> fmt::Display for Snapshot<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+        writeln!(f, "ip: {}", self.ip)?;
+        writeln!(f, "relative-base: {}", self.relative_base)?;
+        writeln!(f, "halted: {}", self.halted)?;
+        writeln!(f, "inputs: {}", join(self.inputs.iter()))?;
+        writeln!(f, "memory: {}", join(self.memory.as_slice().iter()))?;
+        let sparse = self.memory.sparse_cells();
+        writeln!(
+            f,
+            "sparse: {}",
+            join(
+                sparse
+                    .iter()
+                    .map(|(address, value)| format!("{}={}", address, value))
+            )
+        )
+    }
+}
+
+/// parse_value parses the value of a field, or of one element of a list field.
+fn parse_value<T: FromStr>(field: &str, value: &str) -> Result<T, SnapshotError> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| SnapshotError::InvalidValue {
+            field: String::from(field),
+            value: String::from(value),
+        })
+}
+
+fn parse_list<T: FromStr>(field: &str, list: &str) -> Result<Vec<T>, SnapshotError> {
+    if list.trim().is_empty() {
+        return Ok(vec![]);
+    }
+    list.split(',')
+        .map(|value| parse_value(field, value))
+        .collect()
+}
+
+impl<W: Word> FromStr for Snapshot<W> {
+    type Err = SnapshotError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+        match lines.next() {
+            Some(HEADER) => (),
+            header => {
+                return Err(SnapshotError::UnsupportedVersion(String::from(
+                    header.unwrap_or(""),
+                )))
+            }
+        }
+        let mut values: [Option<&str>; FIELDS.len()] = [None; FIELDS.len()];
+        for line in lines {
+            let (field, value) = line
+                .split_once(':')
+                .ok_or_else(|| SnapshotError::UnknownField(String::from(line)))?;
+            let index = FIELDS
+                .iter()
+                .position(|known| *known == field)
+                .ok_or_else(|| SnapshotError::UnknownField(String::from(field)))?;
+            if values[index].replace(value).is_some() {
+                return Err(SnapshotError::DuplicateField(String::from(field)));
+            }
+        }
+        let value = |field: &'static str| {
+            FIELDS
+                .iter()
+                .position(|known| *known == field)
+                .and_then(|index| values[index])
+                .ok_or(SnapshotError::MissingField(field))
+        };
+
+        let ip = parse_value("ip", value("ip")?)?;
+        let relative_base = parse_value("relative-base", value("relative-base")?)?;
+        let halted = parse_value("halted", value("halted")?)?;
+        let inputs: Vec<W> = parse_list("inputs", value("inputs")?)?;
+        let mut memory = Memory::from(&parse_list::<W>("memory", value("memory")?)?[..]);
+        for cell in parse_list::<String>("sparse", value("sparse")?)? {
+            let (address, value) =
+                cell.split_once('=')
+                    .ok_or_else(|| SnapshotError::InvalidValue {
+                        field: String::from("sparse"),
+                        value: cell.clone(),
+                    })?;
+            memory.write(
+                parse_value("sparse", address)?,
+                parse_value("sparse", value)?,
+            );
+        }
+        Ok(Snapshot {
+            memory,
+            ip,
+            relative_base,
+            halted,
+            inputs: VecDeque::from(inputs),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::assembler::assemble;
+    use crate::intcode::{Event, Machine};
+
+    #[test]
+    fn test_format() {
+        let mut memory: Memory = Memory::from(&[109, 1, 203, -1][..]);
+        memory.write(1 << 22, 7);
+        let snapshot = Snapshot {
+            memory,
+            ip: 2,
+            relative_base: 1,
+            halted: false,
+            inputs: VecDeque::from(vec![3, -1]),
+        };
+        let text = "intcode-snapshot 1\n\
+                    ip: 2\n\
+                    relative-base: 1\n\
+                    halted: false\n\
+                    inputs: 3,-1\n\
+                    memory: 109,1,203,-1\n\
+                    sparse: 4194304=7\n";
+        assert_eq!(snapshot.to_string(), text);
+        assert_eq!(text.parse(), Ok(snapshot));
+    }
+
+    #[test]
+    fn test_invalid_snapshots() {
+        let text = "intcode-snapshot 1\nip: 0\nrelative-base: 0\nhalted: false\ninputs:\n";
+        assert_eq!(
+            (String::from(text) + "memory: 99\nsparse:\n").parse::<Snapshot>(),
+            Ok(Snapshot {
+                memory: Memory::from(&[99][..]),
+                ip: 0,
+                relative_base: 0,
+                halted: false,
+                inputs: VecDeque::new(),
+            })
+        );
+        assert_eq!(
+            text.parse::<Snapshot>(),
+            Err(SnapshotError::MissingField("memory"))
+        );
+        assert_eq!(
+            (String::from(text) + "memory: 99,x\nsparse:\n").parse::<Snapshot>(),
+            Err(SnapshotError::InvalidValue {
+                field: String::from("memory"),
+                value: String::from("x"),
+            })
+        );
+        assert_eq!(
+            "intcode-snapshot 2\n".parse::<Snapshot>(),
+            Err(SnapshotError::UnsupportedVersion(String::from(
+                "intcode-snapshot 2"
+            )))
+        );
+    }
+
+    #[test]
+    fn test_resume_from_disk() {
+        let program = assemble(
+            "
+            loop:  IN  [value]
+                   JF  [value], #end
+                   MUL [value], #2, [value]
+                   OUT [value]
+                   JT  #1, #loop
+            end:   HLT
+            value: DATA 0
+            ",
+        )
+        .unwrap();
+        let mut machine = Machine::new(&program);
+        machine.push_input(5);
+        assert_eq!(machine.run_until_event(), Ok(Event::Output(10)));
+        machine.push_input(6);
+        machine.push_input(0);
+
+        let path = std::env::temp_dir().join(format!("intcode-{}.snapshot", std::process::id()));
+        machine.snapshot().save(&path).unwrap();
+        let mut resumed = Machine::from_snapshot(Snapshot::load(&path).unwrap());
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(resumed.snapshot(), machine.snapshot());
+        assert_eq!(resumed.run_until_event(), Ok(Event::Output(12)));
+        assert_eq!(resumed.run_until_event(), Ok(Event::Halted));
+        // The original machine was not affected by the resumed one.
+        assert_eq!(machine.run_until_event(), Ok(Event::Output(12)));
+    }
+}
@@ -0,0 +1,509 @@
+// Symbolic execution of Intcode programs computing without inputs, like the ones of
+// https://adventofcode.com/2019/day/2: some cells hold named symbols instead of numbers, and the
+// arithmetic on them builds expressions which can be simplified to polynomials.
+//
+// Only additions and multiplications can work on symbols. Comparisons, jumps, the relative base
+// and the addresses written to must be known numbers. Reading through a symbolic address gives the
+// opaque expression `[address]`.
+use super::{Instruction, IntcodeError, Memory, Mode, Operation};
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fmt;
+
+/// MAX_STEPS is the number of instructions executed before giving up on a program which does not
+/// halt.
+const MAX_STEPS: usize = 1_000_000;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Expr {
+    Constant(i64),
+    Symbol(String),
+    /// The cell at an address known only symbolically.
+    Load(Box<Expr>),
+    Add(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+}
+
+/// The default expression is the constant 0, the value of the cells never written.
+impl Default for Expr {
+    fn default() -> Self {
+        Expr::Constant(0)
+    }
+}
+
+impl Expr {
+    pub fn as_constant(&self) -> Option<i64> {
+        match self {
+            Expr::Constant(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// polynomial expands the expression, symbols and loads being its variables. It returns None
+    /// if a coefficient overflows.
+    pub fn polynomial(&self) -> Option<Polynomial> {
+        match self {
+            Expr::Constant(value) => Some(Polynomial::constant(*value)),
+            Expr::Symbol(_) | Expr::Load(_) => Some(Polynomial::variable(self.to_string())),
+            Expr::Add(a, b) => a.polynomial()?.checked_add(&b.polynomial()?),
+            Expr::Mul(a, b) => a.polynomial()?.checked_mul(&b.polynomial()?),
+        }
+    }
+
+    fn checked_add(self, other: Expr) -> Option<Expr> {
+        Some(match (self, other) {
+            (Expr::Constant(a), Expr::Constant(b)) => Expr::Constant(a.checked_add(b)?),
+            (Expr::Constant(0), e) | (e, Expr::Constant(0)) => e,
+            (a, b) => Expr::Add(Box::new(a), Box::new(b)),
+        })
+    }
+
+    fn checked_mul(self, other: Expr) -> Option<Expr> {
+        Some(match (self, other) {
+            (Expr::Constant(a), Expr::Constant(b)) => Expr::Constant(a.checked_mul(b)?),
+            (Expr::Constant(0), _) | (_, Expr::Constant(0)) => Expr::Constant(0),
+            (Expr::Constant(1), e) | (e, Expr::Constant(1)) => e,
+            (a, b) => Expr::Mul(Box::new(a), Box::new(b)),
+        })
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::Constant(value) => write!(f, "{}", value),
+            Expr::Symbol(name) => write!(f, "{}", name),
+            Expr::Load(address) => write!(f, "[{}]", address),
+            Expr::Add(a, b) => write!(f, "({} + {})", a, b),
+            Expr::Mul(a, b) => write!(f, "{} * {}", a, b),
+        }
+    }
+}
+
+/// Polynomial is a sum of monomials, each a coefficient times a product of variables.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Polynomial {
+    /// The coefficients by sorted list of variables, the constant term having no variable.
+    terms: BTreeMap<Vec<String>, i64>,
+}
+
+impl Polynomial {
+    pub fn constant(value: i64) -> Self {
+        let mut polynomial = Polynomial::default();
+        if value != 0 {
+            polynomial.terms.insert(vec![], value);
+        }
+        polynomial
+    }
+
+    pub fn variable(name: String) -> Self {
+        Polynomial {
+            terms: vec![(vec![name], 1)].into_iter().collect(),
+        }
+    }
+
+    /// coefficient returns the coefficient of the product of `variables`.
+    pub fn coefficient(&self, variables: &[&str]) -> i64 {
+        let mut key: Vec<String> = variables.iter().map(|v| v.to_string()).collect();
+        key.sort();
+        self.terms.get(&key).copied().unwrap_or(0)
+    }
+
+    /// degree returns the highest number of variables multiplied in a term, 0 for a constant.
+    pub fn degree(&self) -> usize {
+        self.terms.keys().map(|v| v.len()).max().unwrap_or(0)
+    }
+
+    /// evaluate returns the value of the polynomial, or None if a variable has no value or the
+    /// result overflows.
+    pub fn evaluate(&self, values: &HashMap<&str, i64>) -> Option<i64> {
+        self.terms.iter().try_fold(0i64, |sum, (variables, c)| {
+            let term = variables.iter().try_fold(*c, |product, v| {
+                product.checked_mul(*values.get(v.as_str())?)
+            })?;
+            sum.checked_add(term)
+        })
+    }
+
+    fn insert(&mut self, variables: Vec<String>, coefficient: i64) -> Option<()> {
+        let sum = self
+            .terms
+            .get(&variables)
+            .unwrap_or(&0)
+            .checked_add(coefficient)?;
+        if sum == 0 {
+            self.terms.remove(&variables);
+        } else {
+            self.terms.insert(variables, sum);
+        }
+        Some(())
+    }
+
+    pub fn checked_add(&self, other: &Polynomial) -> Option<Polynomial> {
+        let mut sum = self.clone();
+        for (variables, coefficient) in &other.terms {
+            sum.insert(variables.clone(), *coefficient)?;
+        }
+        Some(sum)
+    }
+
+    pub fn checked_mul(&self, other: &Polynomial) -> Option<Polynomial> {
+        let mut product = Polynomial::default();
+        for (v1, c1) in &self.terms {
+            for (v2, c2) in &other.terms {
+                let mut variables: Vec<String> = v1.iter().chain(v2).cloned().collect();
+                variables.sort();
+                product.insert(variables, c1.checked_mul(*c2)?)?;
+            }
+        }
+        Some(product)
+    }
+}
+
+impl fmt::Display for Polynomial {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.terms.is_empty() {
+            return write!(f, "0");
+        }
+        // The terms of highest degree first, the constant last.
+        let mut terms: Vec<(&Vec<String>, &i64)> = self.terms.iter().collect();
+        terms.sort_by(|(v1, _), (v2, _)| v2.len().cmp(&v1.len()).then(v1.cmp(v2)));
+        for (i, (variables, coefficient)) in terms.into_iter().enumerate() {
+            let sign = if *coefficient < 0 { "-" } else { "+" };
+            match (i, sign) {
+                (0, "+") => (),
+                (0, _) => write!(f, "-")?,
+                _ => write!(f, " {} ", sign)?,
+            }
+            let magnitude = coefficient.unsigned_abs();
+            if variables.is_empty() {
+                write!(f, "{}", magnitude)?;
+            } else {
+                if magnitude != 1 {
+                    write!(f, "{}*", magnitude)?;
+                }
+                write!(f, "{}", variables.join("*"))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SymbolicError {
+    Intcode(IntcodeError),
+    /// The instruction at `address` needs a known number where it found `expr`.
+    NotConstant {
+        address: usize,
+        expr: Expr,
+    },
+    /// The operation at `address` cannot run without inputs or outputs.
+    Unsupported {
+        address: usize,
+        operation: Operation,
+    },
+    /// The program did not halt after MAX_STEPS instructions.
+    TooManySteps,
+}
+
+impl fmt::Display for SymbolicError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SymbolicError::Intcode(error) => write!(f, "{}", error),
+            SymbolicError::NotConstant { address, expr } => write!(
+                f,
+                "the instruction at address {} needs a number, found {}",
+                address, expr
+            ),
+            SymbolicError::Unsupported { address, operation } => write!(
+                f,
+                "{} at address {} is not supported symbolically",
+                operation.mnemonic(),
+                address
+            ),
+            SymbolicError::TooManySteps => {
+                write!(f, "the program did not halt after {} steps", MAX_STEPS)
+            }
+        }
+    }
+}
+
+impl Error for SymbolicError {}
+
+impl From<IntcodeError> for SymbolicError {
+    fn from(error: IntcodeError) -> Self {
+        SymbolicError::Intcode(error)
+    }
+}
+
+/// SymbolicMachine runs a program whose memory holds expressions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolicMachine {
+    memory: Memory<Expr>,
+    ip: usize,
+    relative_base: i64,
+    halted: bool,
+}
+
+impl SymbolicMachine {
+    /// new loads `program` and replaces the cells of `symbols` by their names.
+    pub fn new(program: &[i64], symbols: &[(usize, &str)]) -> Self {
+        let constants: Vec<Expr> = program.iter().map(|v| Expr::Constant(*v)).collect();
+        let mut memory = Memory::from(&constants[..]);
+        for (address, name) in symbols {
+            memory.write(*address, Expr::Symbol(name.to_string()));
+        }
+        SymbolicMachine {
+            memory,
+            ip: 0,
+            relative_base: 0,
+            halted: false,
+        }
+    }
+
+    pub fn read(&self, address: usize) -> Expr {
+        self.memory.read(address)
+    }
+
+    fn write(&mut self, address: usize, value: Expr) {
+        self.memory.write(address, value);
+    }
+
+    fn constant(&self, expr: &Expr) -> Result<i64, SymbolicError> {
+        expr.as_constant()
+            .ok_or_else(|| SymbolicError::NotConstant {
+                address: self.ip,
+                expr: expr.clone(),
+            })
+    }
+
+    fn to_address(&self, target: i64) -> Result<usize, SymbolicError> {
+        if target < 0 {
+            return Err(IntcodeError::NegativeAddress {
+                address: self.ip,
+                target,
+            }
+            .into());
+        }
+        Ok(target as usize)
+    }
+
+    /// decode decodes the instruction under the instruction pointer, whose opcode must be known.
+    fn decode(&self) -> Result<(Operation, Vec<(Mode, Expr)>), SymbolicError> {
+        let opcode = self.constant(&self.read(self.ip))?;
+        let instruction =
+            Instruction::decode(&Memory::from(&[opcode][..]), 0).map_err(|error| match error {
+                IntcodeError::UnknownOpcode { opcode, .. } => IntcodeError::UnknownOpcode {
+                    address: self.ip,
+                    opcode,
+                },
+                IntcodeError::InvalidParameterMode {
+                    parameter, mode, ..
+                } => IntcodeError::InvalidParameterMode {
+                    address: self.ip,
+                    parameter,
+                    mode,
+                },
+                error => error,
+            })?;
+        let parameters = instruction
+            .parameters
+            .iter()
+            .enumerate()
+            .map(|(i, parameter)| (parameter.mode, self.read(self.ip + 1 + i)))
+            .collect();
+        Ok((instruction.operation, parameters))
+    }
+
+    /// address_of returns the address a parameter points to, symbolically if it is not known.
+    fn address_of(&self, mode: Mode, value: &Expr, index: usize) -> Result<Expr, SymbolicError> {
+        match mode {
+            Mode::Position => Ok(value.clone()),
+            Mode::Relative => Expr::Constant(self.relative_base)
+                .checked_add(value.clone())
+                .ok_or_else(|| IntcodeError::Overflow { address: self.ip }.into()),
+            Mode::Immediate => Err(IntcodeError::WriteInImmediateMode {
+                address: self.ip,
+                parameter: index,
+            }
+            .into()),
+        }
+    }
+
+    fn value_of(&self, parameters: &[(Mode, Expr)], index: usize) -> Result<Expr, SymbolicError> {
+        let (mode, value) = &parameters[index];
+        if *mode == Mode::Immediate {
+            return Ok(value.clone());
+        }
+        let address = self.address_of(*mode, value, index)?;
+        match address.as_constant() {
+            Some(target) => Ok(self.read(self.to_address(target)?)),
+            None => Ok(Expr::Load(Box::new(address))),
+        }
+    }
+
+    fn target_of(&self, parameters: &[(Mode, Expr)], index: usize) -> Result<usize, SymbolicError> {
+        let (mode, value) = &parameters[index];
+        let address = self.address_of(*mode, value, index)?;
+        self.to_address(self.constant(&address)?)
+    }
+
+    /// step executes the instruction under the instruction pointer.
+    pub fn step(&mut self) -> Result<(), SymbolicError> {
+        let address = self.ip;
+        let (operation, parameters) = self.decode()?;
+        let mut next = address + parameters.len() + 1;
+        let overflow = || SymbolicError::Intcode(IntcodeError::Overflow { address });
+        match operation {
+            Operation::Add | Operation::Mul => {
+                let (a, b) = (
+                    self.value_of(&parameters, 0)?,
+                    self.value_of(&parameters, 1)?,
+                );
+                let value = match operation {
+                    Operation::Add => a.checked_add(b),
+                    _ => a.checked_mul(b),
+                }
+                .ok_or_else(overflow)?;
+                let target = self.target_of(&parameters, 2)?;
+                self.write(target, value);
+            }
+            Operation::LessThan | Operation::Equals => {
+                let a = self.constant(&self.value_of(&parameters, 0)?)?;
+                let b = self.constant(&self.value_of(&parameters, 1)?)?;
+                let value = match operation {
+                    Operation::LessThan => a < b,
+                    _ => a == b,
+                };
+                let target = self.target_of(&parameters, 2)?;
+                self.write(target, Expr::Constant(value as i64));
+            }
+            Operation::JumpIfTrue | Operation::JumpIfFalse => {
+                let condition = self.constant(&self.value_of(&parameters, 0)?)?;
+                if (condition != 0) == (operation == Operation::JumpIfTrue) {
+                    let target = self.constant(&self.value_of(&parameters, 1)?)?;
+                    next = self.to_address(target)?;
+                }
+            }
+            Operation::AdjustRelativeBase => {
+                let offset = self.constant(&self.value_of(&parameters, 0)?)?;
+                self.relative_base = self
+                    .relative_base
+                    .checked_add(offset)
+                    .ok_or_else(overflow)?;
+            }
+            Operation::Ins | Operation::Out => {
+                return Err(SymbolicError::Unsupported { address, operation })
+            }
+            Operation::Halt => self.halted = true,
+        }
+        self.ip = next;
+        Ok(())
+    }
+
+    /// run executes the program until it halts or the instruction pointer leaves the memory.
+    pub fn run(&mut self) -> Result<(), SymbolicError> {
+        for _ in 0..MAX_STEPS {
+            if self.halted || self.ip >= self.memory.len() {
+                return Ok(());
+            }
+            self.step()?;
+        }
+        Err(SymbolicError::TooManySteps)
+    }
+}
+
+/// output_polynomial runs `program` with the `symbols` cells left unknown and returns the final
+/// value of the cell at `output` as a polynomial of the symbols.
+pub fn output_polynomial(
+    program: &[i64],
+    symbols: &[(usize, &str)],
+    output: usize,
+) -> Result<Option<Polynomial>, SymbolicError> {
+    let mut machine = SymbolicMachine::new(program, symbols);
+    machine.run()?;
+    Ok(machine.read(output).polynomial())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::parse_program;
+
+    fn symbol(name: &str) -> Expr {
+        Expr::Symbol(String::from(name))
+    }
+
+    #[test]
+    fn test_expression_tree() {
+        // [0] = (a + b) * 3
+        let program = vec![1, 9, 10, 11, 1002, 11, 3, 0, 99, 0, 0, 0];
+        let mut machine = SymbolicMachine::new(&program, &[(9, "a"), (10, "b")]);
+        machine.run().unwrap();
+        let expected = Expr::Mul(
+            Box::new(Expr::Add(Box::new(symbol("a")), Box::new(symbol("b")))),
+            Box::new(Expr::Constant(3)),
+        );
+        assert_eq!(machine.read(0), expected);
+        assert_eq!(expected.to_string(), "(a + b) * 3");
+        assert_eq!(expected.polynomial().unwrap().to_string(), "3*a + 3*b");
+    }
+
+    #[test]
+    fn test_polynomial() {
+        let a = Polynomial::variable(String::from("a"));
+        let b = Polynomial::variable(String::from("b"));
+        let sum = a
+            .checked_add(&b)
+            .and_then(|sum| sum.checked_add(&Polynomial::constant(-2)))
+            .unwrap();
+        let square = sum.checked_mul(&sum).unwrap();
+        assert_eq!(square.to_string(), "a*a + 2*a*b + b*b - 4*a - 4*b + 4");
+        assert_eq!(square.degree(), 2);
+        assert_eq!(square.coefficient(&["b", "a"]), 2);
+        let values: HashMap<&str, i64> = vec![("a", 3), ("b", 4)].into_iter().collect();
+        assert_eq!(square.evaluate(&values), Some(25));
+    }
+
+    #[test]
+    fn test_day2_polynomial() {
+        let program: Vec<i64> = parse_program(include_str!("../../input/2019/day2.txt")).unwrap();
+        let polynomial = output_polynomial(&program, &[(1, "noun"), (2, "verb")], 0)
+            .unwrap()
+            .unwrap();
+        assert_eq!(polynomial.to_string(), "259200*noun + verb + 1028256");
+        let values: HashMap<&str, i64> = vec![("noun", 12), ("verb", 2)].into_iter().collect();
+        assert_eq!(polynomial.evaluate(&values), Some(4_138_658));
+        let values: HashMap<&str, i64> = vec![("noun", 72), ("verb", 64)].into_iter().collect();
+        assert_eq!(polynomial.evaluate(&values), Some(19_690_720));
+    }
+
+    #[test]
+    fn test_far_address() {
+        // [1 << 40] = [9] + 1, [0] = [1 << 40] * 2
+        let far = 1 << 40;
+        let program = vec![1001, 9, 1, far, 102, 2, far, 0, 99, 0];
+        let polynomial = output_polynomial(&program, &[(9, "a")], 0)
+            .unwrap()
+            .unwrap();
+        assert_eq!(polynomial.to_string(), "2*a + 2");
+        let mut machine = SymbolicMachine::new(&program, &[(9, "a"), (far as usize, "b")]);
+        assert_eq!(machine.read(far as usize), symbol("b"));
+        machine.run().unwrap();
+        assert_eq!(machine.read(far as usize).to_string(), "(a + 1)");
+    }
+
+    #[test]
+    fn test_symbolic_condition() {
+        // JT [a], #0
+        let program = vec![1005, 3, 0, 0, 99];
+        let mut machine = SymbolicMachine::new(&program, &[(3, "a")]);
+        assert_eq!(
+            machine.run(),
+            Err(SymbolicError::NotConstant {
+                address: 0,
+                expr: symbol("a")
+            })
+        );
+    }
+}
@@ -0,0 +1,210 @@
+// Execution tracing and profiling for the Intcode machine, see Machine::enable_trace.
+use super::{Instruction, Operation, Word};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// TRACE_COLUMN is where the resolved operands of an instruction start in a trace line.
+const TRACE_COLUMN: usize = 40;
+/// HOT_ADDRESSES is the number of addresses listed in the summary of a profile.
+const HOT_ADDRESSES: usize = 5;
+
+/// Operand is a parameter of an executed instruction, resolved against the machine state.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operand<W = i64> {
+    /// The value read by the instruction.
+    Value(W),
+    /// The address written by the instruction.
+    Address(usize),
+}
+
+/// TraceEntry is an executed instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEntry<W = i64> {
+    pub address: usize,
+    pub instruction: Instruction<W>,
+    /// The operands in parameter order. Operands which were not needed and could not be
+    /// resolved, like the target of a jump not taken pointing before the memory, are left out.
+    pub operands: Vec<Operand<W>>,
+    /// The cells written by the instruction, with their new value.
+    pub writes: Vec<(usize, W)>,
+}
+
+impl<W: Word> fmt::Display for TraceEntry<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let code = format!("{:>5}: {}", self.address, self.instruction);
+        let operands: Vec<String> = self
+            .operands
+            .iter()
+            .map(|operand| match operand {
+                Operand::Value(value) => value.to_string(),
+                Operand::Address(address) => format!("@{}", address),
+            })
+            .collect();
+        if operands.is_empty() {
+            return write!(f, "{}", code);
+        }
+        write!(
+            f,
+            "{:<width$}; {}",
+            code,
+            operands.join(", "),
+            width = TRACE_COLUMN
+        )?;
+        for (address, value) in &self.writes {
+            write!(f, "; [{}] <- {}", address, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Trace is the log of the instructions executed by a machine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trace<W = i64> {
+    entries: Vec<TraceEntry<W>>,
+}
+
+impl<W> Default for Trace<W> {
+    fn default() -> Self {
+        Trace { entries: vec![] }
+    }
+}
+
+impl<W: Word> Trace<W> {
+    pub fn entries(&self) -> &[TraceEntry<W>] {
+        &self.entries
+    }
+
+    pub(crate) fn record(&mut self, entry: TraceEntry<W>) {
+        self.entries.push(entry);
+    }
+
+    pub fn profile(&self) -> Profile {
+        let mut profile = Profile::default();
+        for entry in &self.entries {
+            profile.record(entry.address, entry.instruction.operation);
+        }
+        profile
+    }
+}
+
+impl<W: Word> fmt::Display for Trace<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for entry in &self.entries {
+            writeln!(f, "{}", entry)?;
+        }
+        Ok(())
+    }
+}
+
+/// Profile summarizes a trace: how many instructions were executed, per operation and per
+/// address.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Profile {
+    pub cycles: usize,
+    pub operations: BTreeMap<Operation, usize>,
+    pub addresses: BTreeMap<usize, usize>,
+}
+
+impl Profile {
+    fn record(&mut self, address: usize, operation: Operation) {
+        self.cycles += 1;
+        *self.operations.entry(operation).or_insert(0) += 1;
+        *self.addresses.entry(address).or_insert(0) += 1;
+    }
+
+    /// hot_addresses returns the `count` most executed addresses with their number of executions,
+    /// the most executed first.
+    pub fn hot_addresses(&self, count: usize) -> Vec<(usize, usize)> {
+        let mut addresses: Vec<(usize, usize)> =
+            self.addresses.iter().map(|(a, n)| (*a, *n)).collect();
+        addresses.sort_by(|(a1, n1), (a2, n2)| n2.cmp(n1).then(a1.cmp(a2)));
+        addresses.truncate(count);
+        addresses
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "cycles: {}", self.cycles)?;
+        for (operation, count) in &self.operations {
+            writeln!(f, "{:<4}{}", operation.mnemonic(), count)?;
+        }
+        writeln!(f, "hot addresses:")?;
+        for (address, count) in self.hot_addresses(HOT_ADDRESSES) {
+            writeln!(f, "{:>5}: {}", address, count)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::assembler::assemble;
+    use crate::intcode::{parse_program, Machine};
+    use std::collections::VecDeque;
+
+    fn traced_run(program: &[i64], inputs: Vec<i64>) -> Trace {
+        let mut machine = Machine::new(program);
+        machine.enable_trace();
+        machine
+            .run(&mut VecDeque::from(inputs), &mut vec![])
+            .unwrap();
+        machine.take_trace().unwrap()
+    }
+
+    #[test]
+    fn test_trace_entries() {
+        let trace = traced_run(&[1002, 4, 3, 4, 33], vec![]);
+        assert_eq!(trace.entries().len(), 2);
+        assert_eq!(
+            trace.entries()[0].operands,
+            vec![Operand::Value(33), Operand::Value(3), Operand::Address(4)]
+        );
+        assert_eq!(trace.entries()[0].writes, vec![(4, 99)]);
+        assert_eq!(
+            trace.to_string(),
+            "    0: MUL [4], #3, [4]                 ; 33, 3, @4; [4] <- 99\n\
+             \x20   4: HLT\n"
+        );
+    }
+
+    #[test]
+    fn test_tracing_is_optional() {
+        let mut machine = Machine::new(&[1002, 4, 3, 4, 33]);
+        machine.run(&mut VecDeque::new(), &mut vec![]).unwrap();
+        assert_eq!(machine.take_trace(), None);
+    }
+
+    #[test]
+    fn test_profile() {
+        let program = assemble(
+            "
+                   IN  [value]
+            loop:  ADD [value], #-1, [value]
+                   JT  [value], #loop
+                   HLT
+            value: DATA 0
+            ",
+        )
+        .unwrap();
+        let profile = traced_run(&program, vec![3]).profile();
+        assert_eq!(profile.cycles, 8);
+        assert_eq!(profile.operations[&Operation::Add], 3);
+        assert_eq!(profile.operations[&Operation::Halt], 1);
+        assert_eq!(profile.hot_addresses(2), vec![(2, 3), (6, 3)]);
+    }
+
+    #[test]
+    fn test_day5_profile() {
+        let program: Vec<i64> = parse_program(include_str!("../../input/2019/day5.txt")).unwrap();
+        let diagnostics = traced_run(&program, vec![1]).profile();
+        let thermal = traced_run(&program, vec![5]).profile();
+        assert!(!diagnostics.operations.contains_key(&Operation::LessThan));
+        assert!(thermal.operations[&Operation::LessThan] > 0);
+        assert_eq!(
+            diagnostics.to_string().lines().next(),
+            Some(format!("cycles: {}", diagnostics.cycles).as_str())
+        );
+    }
+}
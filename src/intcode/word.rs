@@ -0,0 +1,118 @@
+use std::convert::TryFrom;
+use std::fmt::{Debug, Display};
+use std::hash::Hash;
+use std::str::FromStr;
+
+/// Word is the type of a memory cell of an Intcode machine.
+pub trait Word: Clone + Debug + Default + Display + Eq + Ord + Hash + FromStr {
+    fn zero() -> Self;
+
+    fn one() -> Self;
+
+    /// to_i64 returns the word as an i64, or None if it does not fit.
+    fn to_i64(&self) -> Option<i64>;
+
+    fn checked_add(&self, other: &Self) -> Option<Self>;
+
+    fn checked_mul(&self, other: &Self) -> Option<Self>;
+
+    fn is_zero(&self) -> bool {
+        self.to_i64() == Some(0)
+    }
+
+    fn from_bool(value: bool) -> Self {
+        if value {
+            Self::one()
+        } else {
+            Self::zero()
+        }
+    }
+}
+
+macro_rules! primitive_word {
+    ($($t:ty),*) => {
+        $(
+            impl Word for $t {
+                fn zero() -> Self {
+                    0
+                }
+
+                fn one() -> Self {
+                    1
+                }
+
+                fn to_i64(&self) -> Option<i64> {
+                    i64::try_from(*self).ok()
+                }
+
+                fn checked_add(&self, other: &Self) -> Option<Self> {
+                    <$t>::checked_add(*self, *other)
+                }
+
+                fn checked_mul(&self, other: &Self) -> Option<Self> {
+                    <$t>::checked_mul(*self, *other)
+                }
+
+                fn is_zero(&self) -> bool {
+                    *self == 0
+                }
+            }
+        )*
+    };
+}
+
+primitive_word!(i32, i64, i128);
+
+#[cfg(feature = "bigint")]
+impl Word for num_bigint::BigInt {
+    fn zero() -> Self {
+        num_bigint::BigInt::from(0)
+    }
+
+    fn one() -> Self {
+        num_bigint::BigInt::from(1)
+    }
+
+    fn to_i64(&self) -> Option<i64> {
+        i64::try_from(self).ok()
+    }
+
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        Some(self + other)
+    }
+
+    fn checked_mul(&self, other: &Self) -> Option<Self> {
+        Some(self * other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checked_operations() {
+        assert_eq!(Word::checked_add(&1i64, &2), Some(3));
+        assert_eq!(Word::checked_mul(&i64::MAX, &2), None);
+        assert_eq!(Word::checked_mul(&i32::MAX, &2), None);
+    }
+
+    #[test]
+    fn test_to_i64() {
+        assert_eq!(Word::to_i64(&-5i32), Some(-5));
+        assert_eq!(Word::to_i64(&(i128::from(i64::MAX) + 1)), None);
+        assert!(Word::is_zero(&0i128));
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn test_bigint() {
+        use num_bigint::BigInt;
+        let big = BigInt::from(i64::MAX);
+        assert_eq!(
+            big.checked_mul(&big),
+            Some(BigInt::from(i64::MAX) * i64::MAX)
+        );
+        assert_eq!(big.checked_mul(&big).unwrap().to_i64(), None);
+    }
+}
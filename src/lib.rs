@@ -5,5 +5,6 @@ mod day2;
 mod day3;
 mod day4;
 mod day5;
+pub mod intcode;
 
 aoc_lib! { year = 2019 }